@@ -3,11 +3,130 @@ use anchor_lang::prelude::*;
 /// Custom error types for market operations
 #[error_code]
 pub enum CustomError {
+    #[msg("The resolve window must not start in the past")]
     InvalidResolveTime,
+    #[msg("The resolve window must start before it ends")]
     InvalidResolveWindow,
+    #[msg("The outcome index is out of range for the market")]
     InvalidOutcome,
+    #[msg("The market does not hold enough shares of the outcome")]
     InsufficientOutcomeAvailable,
+    #[msg("The market is already resolved")]
     MarketAlreadyResolved,
+    #[msg("The market is not resolved yet")]
     MarketNotResolvedYet,
+    #[msg("The program metadata is already initialized")]
     MetadataAlreadyInitialized,
+    #[msg("Arithmetic overflow or underflow")]
+    MathOverflow,
+    #[msg("The liquidity parameter must be greater than zero")]
+    InvalidLiquidity,
+    #[msg("The subsidy does not cover the worst-case loss of the market")]
+    InsufficientSubsidy,
+    #[msg("The trade price is outside the slippage limit")]
+    SlippageExceeded,
+    #[msg("The trade has expired")]
+    TradeExpired,
+    #[msg("The amount must be greater than zero")]
+    InvalidAmount,
+    #[msg("The fee is above the maximum fee")]
+    InvalidFee,
+    #[msg("The signer is not allowed to perform this action")]
+    Unauthorized,
+    #[msg("There are no fees to claim")]
+    NoFeesToClaim,
+    #[msg("The number of outcomes must be between 2 and the maximum number of outcomes")]
+    InvalidOutcomeCount,
+    #[msg("The price thresholds must be strictly ascending")]
+    InvalidPriceThresholds,
+    #[msg("The lower bound of the scalar range must be below the upper bound")]
+    InvalidScalarRange,
+    #[msg("The resolve window has not opened yet")]
+    ResolveWindowNotOpen,
+    #[msg("The price was not published within the resolve window")]
+    PriceOutsideResolveWindow,
+    #[msg("The price update is not verified enough for the market")]
+    InsufficientVerificationLevel,
+    #[msg("The resolve window has not closed yet")]
+    ResolveWindowNotClosed,
+    #[msg("The market is voided")]
+    MarketVoided,
+    #[msg("The market is not voided")]
+    MarketNotVoided,
+    #[msg("The position has nothing to refund")]
+    NothingToRefund,
+    #[msg("There is no subsidy left to reclaim")]
+    NoSubsidyToReclaim,
+    #[msg("The position still has a payout or refund to claim")]
+    PositionNotClosable,
+    #[msg("The mint is not the collateral mint of the market")]
+    InvalidCollateralMint,
+    #[msg("The collateral mint is not allowed")]
+    CollateralMintNotAllowed,
+    #[msg("The program is paused")]
+    ProgramPaused,
+    #[msg("The market is paused")]
+    MarketPaused,
+    #[msg("The price feed is not allowed")]
+    PriceFeedNotAllowed,
+    #[msg("The price feed is already allowed")]
+    PriceFeedAlreadyAllowed,
+    #[msg("The allowlist of price feeds is full")]
+    TooManyPriceFeeds,
+    #[msg("The position does not hold enough shares of the outcome")]
+    InsufficientShares,
+    #[msg("No shares of the winning outcome are held")]
+    NoWinningShares,
+    #[msg("Trading must open before it closes, and close no later than the start of the resolve window")]
+    InvalidTradingWindow,
+    #[msg("Trading on the market has not opened yet")]
+    TradingNotOpen,
+    #[msg("Trading on the market has closed")]
+    TradingClosed,
+    #[msg("The maximum confidence ratio must be between 1 and 10000 basis points")]
+    InvalidConfidencePolicy,
+    #[msg("The confidence interval of the price is too wide")]
+    ConfidenceTooWide,
+    #[msg("The price is within the confidence interval of a price threshold")]
+    PriceWithinConfidence,
+    #[msg("The price must be published after the last ambiguous price")]
+    PriceNotAfterAmbiguousPrice,
+    #[msg("The maximum price age must be greater than zero and no greater than the maximum price age of the configuration")]
+    InvalidMaxPriceAge,
+    #[msg("The price is older than the maximum price age of the market")]
+    StalePrice,
+    #[msg("The price update is not for the price feed of the market")]
+    PriceFeedMismatch,
+    #[msg("The oracle account is invalid or holds no price")]
+    InvalidOracleAccount,
+    #[msg("The additional oracles must be of distinct providers, allowed, at most two, with a deviation between 1 and 10000 basis points")]
+    InvalidOracleSources,
+    #[msg("The oracle accounts do not match the oracle sources of the market")]
+    OracleAccountsMismatch,
+    #[msg("The prices of the oracle sources deviate by more than the maximum oracle deviation")]
+    OraclesDisagree,
+    #[msg("The settlement mode is not supported by the oracles or the confidence policy of the market")]
+    InvalidSettlementMode,
+    #[msg("The oracle does not provide a moving average price")]
+    EmaPriceNotSupported,
+    #[msg("The market is not settled on a time-weighted average price")]
+    NotTwapMarket,
+    #[msg("The price must be published after the last observation")]
+    ObservationNotAfterLast,
+    #[msg("Not enough prices were observed to settle the market on their time-weighted average")]
+    InsufficientObservations,
+    #[msg("The market can still be resolved")]
+    MarketResolvable,
+    #[msg("The number of price thresholds does not match the price condition")]
+    InvalidPriceCondition,
+    #[msg("Every outcome must have a label of 1 to 32 bytes")]
+    InvalidOutcomeLabels,
+    #[msg("The account does not have the legacy layout")]
+    NotLegacyAccount,
+    #[msg("The collateral mint has more decimals than the fixed-point math supports")]
+    InvalidCollateralDecimals,
+    #[msg("The price exponent is out of range or the prices cannot be rescaled to the lowest exponent")]
+    InvalidPriceExponent,
+    #[msg("The mock oracle is only available in localnet builds")]
+    MockOracleDisabled,
 }
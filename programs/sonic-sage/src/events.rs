@@ -0,0 +1,101 @@
+use anchor_lang::prelude::*;
+
+use crate::instructions::quote::TradeSide;
+use crate::state::market::{
+    ConfidencePolicy, MarketKind, OracleKind, OracleSource, PriceCondition, SettlementMode,
+};
+
+/// Emitted when a market is created
+#[event]
+pub struct MarketCreated {
+    pub market: Pubkey,
+    pub id: u64,
+    pub creator: Pubkey,
+    pub collateral_mint: Pubkey,
+    pub kind: MarketKind,
+    pub price_exponent: i32,
+    pub condition: PriceCondition,
+    pub outcome_labels: Vec<String>,
+    pub num_outcomes: u8,
+    pub oracle_kind: OracleKind,
+    pub price_feed_id: String,
+    pub additional_oracles: Vec<OracleSource>,
+    pub max_oracle_deviation_bps: u16,
+    pub settlement_mode: SettlementMode,
+    pub resolve_from: u64,
+    pub resolve_to: u64,
+    pub trading_opens_at: u64,
+    pub trading_closes_at: u64,
+    /// Subsidy received by the market vault, in tokens (with decimals)
+    pub subsidy: u64,
+    pub liquidity_b: u64,
+    pub creator_fee_bps: u16,
+    pub max_price_age: u64,
+    pub allow_partial_verification: bool,
+    pub confidence_policy: ConfidencePolicy,
+    /// Initial prices of the outcomes, scaled by `SCALE`
+    pub prices: Vec<u64>,
+}
+
+/// Emitted when outcome shares are bought or sold
+#[event]
+pub struct Trade {
+    pub market: Pubkey,
+    pub trader: Pubkey,
+    pub side: TradeSide,
+    pub outcome: u8,
+    pub shares: u64,
+    /// Cost of a buy or proceeds of a sell in tokens (with decimals), before fees
+    pub cost: u64,
+    pub protocol_fee: u64,
+    pub creator_fee: u64,
+    /// Prices of the outcomes after the trade, scaled by `SCALE`
+    pub prices: Vec<u64>,
+}
+
+/// Emitted when a market is resolved from an oracle price
+#[event]
+pub struct MarketResolved {
+    pub market: Pubkey,
+    /// Winning outcome of a categorical market
+    pub outcome: Option<u8>,
+    /// Whether the market was voided because the price was ambiguous
+    pub is_voided: bool,
+    /// LONG payout of a scalar market, scaled by `SCALE`
+    pub scalar_payout: u64,
+    /// Settlement price, to be multiplied by `10^exponent`
+    pub price: i128,
+    pub conf: u128,
+    pub exponent: i32,
+    pub publish_time: i64,
+}
+
+/// Emitted when a market that was not resolved in time is voided
+#[event]
+pub struct MarketVoided {
+    pub market: Pubkey,
+    /// Unix timestamp in seconds when the market was voided
+    pub voided_at: i64,
+}
+
+/// Emitted when an oracle price is observed for a market settled on a time-weighted average price
+#[event]
+pub struct ObservationRecorded {
+    pub market: Pubkey,
+    /// Median oracle price, to be multiplied by `10^exponent`
+    pub price: i128,
+    pub conf: u128,
+    pub exponent: i32,
+    pub publish_time: i64,
+    /// Number of observations recorded so far
+    pub observations: u32,
+}
+
+/// Emitted when a position is redeemed after resolution
+#[event]
+pub struct Redeemed {
+    pub market: Pubkey,
+    pub user: Pubkey,
+    /// Amount paid out, in tokens (with decimals)
+    pub amount: u64,
+}
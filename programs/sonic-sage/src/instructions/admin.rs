@@ -0,0 +1,473 @@
+use {
+    anchor_lang::prelude::*,
+    anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use pyth_solana_receiver_sdk::price_update::get_feed_id_from_hex;
+
+use crate::errors::*;
+use crate::math::SCALE_DECIMALS;
+use crate::oracle::check_oracle_kind;
+use crate::program::SonicSage;
+use crate::state::collateral::CollateralMint;
+use crate::state::config::{Config, DEFAULT_MAX_PRICE_AGE, MAX_ALLOWED_PRICE_FEEDS, MAX_FEE_BPS};
+use crate::state::market::{Market, OracleKind, OracleSource};
+use crate::state::mock_oracle::MockOracle;
+
+/// Context accounts for initializing the global configuration
+///
+/// Only the upgrade authority of the program can initialize the configuration,
+/// so that the admin cannot be claimed by whoever gets there first.
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + Config::INIT_SPACE,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, SonicSage>,
+
+    #[account(constraint = program_data.upgrade_authority_address == Some(signer.key()) @ CustomError::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Context accounts for updating the global configuration
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = admin @ CustomError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    pub admin: Signer<'info>,
+}
+
+/// Context accounts for the pending admin to accept the admin authority
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        constraint = config.pending_admin == Some(pending_admin.key()) @ CustomError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    pub pending_admin: Signer<'info>,
+}
+
+/// Context accounts for pausing or unpausing a single market
+#[derive(Accounts)]
+pub struct SetMarketPaused<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+        has_one = admin @ CustomError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+
+    pub admin: Signer<'info>,
+}
+
+/// Context accounts for adding a collateral mint to the allowlist
+#[derive(Accounts)]
+pub struct AddCollateralMint<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+        has_one = admin @ CustomError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + CollateralMint::INIT_SPACE,
+        seeds = [b"collateral", mint.key().as_ref()],
+        bump
+    )]
+    pub collateral_mint: Account<'info, CollateralMint>,
+
+    #[account(
+        init,
+        payer = admin,
+        token::mint = mint,
+        token::authority = protocol_fee_vault,
+        token::token_program = token_program,
+        seeds = [b"protocol_fees", mint.key().as_ref()],
+        bump
+    )]
+    pub protocol_fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Context accounts for updating a collateral mint of the allowlist
+#[derive(Accounts)]
+pub struct UpdateCollateralMint<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+        has_one = admin @ CustomError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral", collateral_mint.mint.as_ref()],
+        bump
+    )]
+    pub collateral_mint: Account<'info, CollateralMint>,
+
+    pub admin: Signer<'info>,
+}
+
+/// Context accounts for setting the price of a mock price feed
+#[derive(Accounts)]
+#[instruction(feed_id: [u8; 32])]
+pub struct SetMockPrice<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+        has_one = admin @ CustomError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + MockOracle::INIT_SPACE,
+        seeds = [b"mock_oracle", feed_id.as_ref()],
+        bump
+    )]
+    pub mock_oracle: Account<'info, MockOracle>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Checks that neither the program nor the market is paused
+///
+/// # Arguments
+///
+/// * `config` - Global configuration of the program
+/// * `market` - Market the instruction operates on
+///
+/// # Errors
+///
+/// Returns error if the program or the market is paused
+pub fn check_not_paused(config: &Config, market: &Market) -> Result<()> {
+    require!(!config.is_paused, CustomError::ProgramPaused);
+    require!(!market.is_paused, CustomError::MarketPaused);
+    Ok(())
+}
+
+/// Initializes the global configuration with the signer as admin
+///
+/// # Arguments
+///
+/// * `ctx` - InitializeConfig context containing required accounts
+/// * `protocol_fee_bps` - Fee charged by the protocol on every trade, in basis points
+///
+/// # Errors
+///
+/// Returns error if the protocol fee is above `MAX_FEE_BPS`
+pub fn initialize_config(ctx: Context<InitializeConfig>, protocol_fee_bps: u16) -> Result<()> {
+    require!(protocol_fee_bps <= MAX_FEE_BPS, CustomError::InvalidFee);
+
+    ctx.accounts.config.admin = ctx.accounts.signer.key();
+    ctx.accounts.config.pending_admin = None;
+    ctx.accounts.config.protocol_fee_bps = protocol_fee_bps;
+    ctx.accounts.config.max_creator_fee_bps = MAX_FEE_BPS;
+    ctx.accounts.config.max_price_age = DEFAULT_MAX_PRICE_AGE;
+    ctx.accounts.config.is_paused = false;
+    ctx.accounts.config.allowed_price_feeds = Vec::new();
+
+    Ok(())
+}
+
+/// Updates the protocol fee charged on every trade
+///
+/// # Arguments
+///
+/// * `ctx` - UpdateConfig context containing required accounts
+/// * `protocol_fee_bps` - Fee charged by the protocol on every trade, in basis points
+///
+/// # Errors
+///
+/// Returns error if the signer is not the admin or the fee is above `MAX_FEE_BPS`
+pub fn set_protocol_fee(ctx: Context<UpdateConfig>, protocol_fee_bps: u16) -> Result<()> {
+    require!(protocol_fee_bps <= MAX_FEE_BPS, CustomError::InvalidFee);
+
+    ctx.accounts.config.protocol_fee_bps = protocol_fee_bps;
+
+    Ok(())
+}
+
+/// Updates the maximum fee a market creator can charge on every trade
+///
+/// Only applies to markets created after the update.
+///
+/// # Arguments
+///
+/// * `ctx` - UpdateConfig context containing required accounts
+/// * `max_creator_fee_bps` - Maximum creator fee, in basis points
+///
+/// # Errors
+///
+/// Returns error if the signer is not the admin or the fee is above `MAX_FEE_BPS`
+pub fn set_max_creator_fee(ctx: Context<UpdateConfig>, max_creator_fee_bps: u16) -> Result<()> {
+    require!(max_creator_fee_bps <= MAX_FEE_BPS, CustomError::InvalidFee);
+
+    ctx.accounts.config.max_creator_fee_bps = max_creator_fee_bps;
+
+    Ok(())
+}
+
+/// Updates the upper bound on the maximum age of an oracle price that market creators can set
+///
+/// Only applies to markets created after the update.
+///
+/// # Arguments
+///
+/// * `ctx` - UpdateConfig context containing required accounts
+/// * `max_price_age` - Upper bound on the maximum price age, in seconds
+///
+/// # Errors
+///
+/// Returns error if the signer is not the admin or the maximum price age is zero
+pub fn set_max_price_age(ctx: Context<UpdateConfig>, max_price_age: u64) -> Result<()> {
+    require!(max_price_age > 0, CustomError::InvalidMaxPriceAge);
+
+    ctx.accounts.config.max_price_age = max_price_age;
+
+    Ok(())
+}
+
+/// Proposes a new admin, who must accept the admin authority before it is transferred
+///
+/// Proposing another admin replaces the pending one.
+///
+/// # Arguments
+///
+/// * `ctx` - UpdateConfig context containing required accounts
+/// * `new_admin` - Public key of the proposed admin
+///
+/// # Errors
+///
+/// Returns error if the signer is not the admin
+pub fn propose_admin(ctx: Context<UpdateConfig>, new_admin: Pubkey) -> Result<()> {
+    ctx.accounts.config.pending_admin = Some(new_admin);
+
+    Ok(())
+}
+
+/// Transfers the admin authority to the pending admin
+///
+/// # Arguments
+///
+/// * `ctx` - AcceptAdmin context containing required accounts
+///
+/// # Errors
+///
+/// Returns error if the signer is not the pending admin
+pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+    ctx.accounts.config.admin = ctx.accounts.pending_admin.key();
+    ctx.accounts.config.pending_admin = None;
+
+    Ok(())
+}
+
+/// Pauses or unpauses market creation, trading, resolution and redemption on all markets
+///
+/// # Arguments
+///
+/// * `ctx` - UpdateConfig context containing required accounts
+/// * `is_paused` - Whether the program is paused
+///
+/// # Errors
+///
+/// Returns error if the signer is not the admin
+pub fn set_paused(ctx: Context<UpdateConfig>, is_paused: bool) -> Result<()> {
+    ctx.accounts.config.is_paused = is_paused;
+
+    Ok(())
+}
+
+/// Pauses or unpauses trading, resolution and redemption on a single market
+///
+/// # Arguments
+///
+/// * `ctx` - SetMarketPaused context containing required accounts
+/// * `is_paused` - Whether the market is paused
+///
+/// # Errors
+///
+/// Returns error if the signer is not the admin
+pub fn set_market_paused(ctx: Context<SetMarketPaused>, is_paused: bool) -> Result<()> {
+    ctx.accounts.market.is_paused = is_paused;
+
+    Ok(())
+}
+
+/// Adds a price feed of an oracle provider to the feeds markets can be created on
+///
+/// A feed is only allowed for the oracle provider it is added with, so that a market cannot
+/// read an allowed feed id from another provider.
+///
+/// # Arguments
+///
+/// * `ctx` - UpdateConfig context containing required accounts
+/// * `oracle_kind` - Oracle provider of the price feed
+/// * `price_feed_id` - Hex encoded price feed id, the account address for push and Switchboard feeds
+///
+/// # Errors
+///
+/// Returns error if the signer is not the admin, the oracle kind is not available in this build,
+/// the feed id is invalid, the feed is already allowed or the allowlist is full
+pub fn add_allowed_price_feed(
+    ctx: Context<UpdateConfig>,
+    oracle_kind: OracleKind,
+    price_feed_id: String,
+) -> Result<()> {
+    check_oracle_kind(oracle_kind)?;
+    let source = OracleSource {
+        kind: oracle_kind,
+        feed_id: get_feed_id_from_hex(&price_feed_id)?,
+    };
+    let allowed_price_feeds = &mut ctx.accounts.config.allowed_price_feeds;
+    require!(!allowed_price_feeds.contains(&source), CustomError::PriceFeedAlreadyAllowed);
+    require!(allowed_price_feeds.len() < MAX_ALLOWED_PRICE_FEEDS, CustomError::TooManyPriceFeeds);
+
+    allowed_price_feeds.push(source);
+
+    Ok(())
+}
+
+/// Removes a price feed of an oracle provider from the feeds markets can be created on
+///
+/// Existing markets on the feed can still be traded and resolved.
+///
+/// # Arguments
+///
+/// * `ctx` - UpdateConfig context containing required accounts
+/// * `oracle_kind` - Oracle provider of the price feed
+/// * `price_feed_id` - Hex encoded price feed id, the account address for push and Switchboard feeds
+///
+/// # Errors
+///
+/// Returns error if the signer is not the admin, the feed id is invalid or the feed is not allowed
+pub fn remove_allowed_price_feed(
+    ctx: Context<UpdateConfig>,
+    oracle_kind: OracleKind,
+    price_feed_id: String,
+) -> Result<()> {
+    let source = OracleSource {
+        kind: oracle_kind,
+        feed_id: get_feed_id_from_hex(&price_feed_id)?,
+    };
+    let allowed_price_feeds = &mut ctx.accounts.config.allowed_price_feeds;
+    let index = allowed_price_feeds
+        .iter()
+        .position(|allowed| *allowed == source)
+        .ok_or(CustomError::PriceFeedNotAllowed)?;
+
+    allowed_price_feeds.swap_remove(index);
+
+    Ok(())
+}
+
+/// Adds a collateral mint to the allowlist and creates its protocol fee vault
+///
+/// # Arguments
+///
+/// * `ctx` - AddCollateralMint context containing required accounts
+///
+/// # Errors
+///
+/// Returns error if the signer is not the admin, the mint is already on the allowlist
+/// or has more decimals than `SCALE_DECIMALS`
+pub fn add_collateral_mint(ctx: Context<AddCollateralMint>) -> Result<()> {
+    require!(ctx.accounts.mint.decimals <= SCALE_DECIMALS, CustomError::InvalidCollateralDecimals);
+
+    ctx.accounts.collateral_mint.mint = ctx.accounts.mint.key();
+    ctx.accounts.collateral_mint.decimals = ctx.accounts.mint.decimals;
+    ctx.accounts.collateral_mint.is_enabled = true;
+
+    Ok(())
+}
+
+/// Enables or disables the creation of new markets with a collateral mint
+///
+/// # Arguments
+///
+/// * `ctx` - UpdateCollateralMint context containing required accounts
+/// * `is_enabled` - Whether new markets can be created with the collateral mint
+///
+/// # Errors
+///
+/// Returns error if the signer is not the admin
+pub fn set_collateral_mint_enabled(ctx: Context<UpdateCollateralMint>, is_enabled: bool) -> Result<()> {
+    ctx.accounts.collateral_mint.is_enabled = is_enabled;
+
+    Ok(())
+}
+
+/// Sets the price of a mock price feed, creating its mock oracle on first use
+///
+/// Markets with the `Mock` oracle kind resolve from this price, for testing on localnet.
+/// The mock oracle is only available in builds with the `localnet` feature.
+///
+/// # Arguments
+///
+/// * `ctx` - SetMockPrice context containing required accounts
+/// * `feed_id` - ID of the mocked price feed
+/// * `price` - Price, standing for `price * 10^exponent`
+/// * `conf` - Confidence interval of the price
+/// * `exponent` - Exponent of the price and confidence interval
+/// * `publish_time` - Publish time of the price (unix timestamp in seconds)
+///
+/// # Errors
+///
+/// Returns error if the signer is not the admin or the program is not built for localnet
+pub fn set_mock_price(
+    ctx: Context<SetMockPrice>,
+    feed_id: [u8; 32],
+    price: i64,
+    conf: u64,
+    exponent: i32,
+    publish_time: i64,
+) -> Result<()> {
+    check_oracle_kind(OracleKind::Mock)?;
+    ctx.accounts.mock_oracle.feed_id = feed_id;
+    ctx.accounts.mock_oracle.price = price;
+    ctx.accounts.mock_oracle.conf = conf;
+    ctx.accounts.mock_oracle.exponent = exponent;
+    ctx.accounts.mock_oracle.publish_time = publish_time;
+
+    Ok(())
+}
@@ -2,41 +2,47 @@ use {
     anchor_lang::prelude::*,
     anchor_spl::{
         associated_token::AssociatedToken,
-        token::{transfer, Mint, Token, TokenAccount, Transfer},
+        token_interface::{Mint, TokenAccount, TokenInterface},
     },
 };
 
 use crate::errors::*;
+use crate::events::Trade;
+use crate::instructions::quote::TradeSide;
+use crate::instructions::admin::check_not_paused;
+use crate::math::{
+    from_token_amount, lmsr_cost, lmsr_prices, lmsr_quantity_for_cost, to_token_amount, Rounding, SCALE,
+};
+use crate::state::config::{Config, BPS_DENOMINATOR};
 use crate::state::market::Market;
 use crate::state::outcome::OutcomeAccount;
-
-/// Liquidity parameter for the LMSR (Logarithmic Market Scoring Rule) pricing model
-/// Controls market price sensitivity - higher values create less price movement per trade
-const LIQUIDITY_CONSTANT: u64 = 50;
+use crate::transfer::{gross_amount, received_amount, transfer_tokens};
 
 // Context for buying and selling outcome tokens
+#[event_cpi]
 #[derive(Accounts)]
 #[instruction(outcome:u8, amount: u64)]
 pub struct BuySellOutcome<'info> {
     #[account(mut)]
     pub market: Account<'info, Market>,
 
-    #[account(mut)]
-    pub subsidy_mint: Account<'info, Mint>,
+    #[account(address = market.collateral_mint @ CustomError::InvalidCollateralMint)]
+    pub subsidy_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         token::mint = subsidy_mint,
         token::authority = signer
     )]
-    pub signer_token_account: Account<'info, TokenAccount>,
+    pub signer_token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
-        seeds = [b"token"],
+        token::mint = subsidy_mint,
+        seeds = [b"vault", market.key().as_ref()],
         bump
     )]
-    pub program_token_account: Account<'info, TokenAccount>,
+    pub market_vault: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         init_if_needed,
@@ -47,10 +53,32 @@ pub struct BuySellOutcome<'info> {
     )]
     pub outcome_account: Account<'info, OutcomeAccount>,
 
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(
+        mut,
+        token::mint = subsidy_mint,
+        seeds = [b"protocol_fees", subsidy_mint.key().as_ref()],
+        bump
+    )]
+    pub protocol_fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = subsidy_mint,
+        seeds = [b"creator_fees", market.key().as_ref()],
+        bump
+    )]
+    pub creator_fee_vault: InterfaceAccount<'info, TokenAccount>,
+
     #[account(mut)]
     pub signer: Signer<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
@@ -58,156 +86,742 @@ pub struct BuySellOutcome<'info> {
 /// Buy outcome shares for a given market
 ///
 /// Uses LMSR (Logarithmic Market Scoring Rule) to calculate token price
-/// and executes token transfers between user and program accounts
+/// and executes token transfers between user and program accounts.
+/// The cost is rounded up in favour of the market, and the protocol
+/// and creator fees are charged on top of it. With a Token-2022 mint
+/// that charges a transfer fee, each transfer is grossed up so the market
+/// and fee vaults receive their amounts in full, and the trader pays the transfer fees.
 ///
 /// # Arguments
 ///
 /// * `ctx` - BuySellOutcome context containing required accounts
-/// * `outcome_idx` - Index of outcome to buy (0 or 1)
+/// * `outcome_idx` - Index of outcome to buy (less than the market's number of outcomes)
 /// * `num_shares` - Number of outcome shares to purchase
+/// * `max_cost` - Maximum amount of tokens (with decimals) debited from the trader, including trading fees
+///   and any transfer fee of a Token-2022 mint
+/// * `expires_at` - Optional unix timestamp (in seconds) after which the trade is rejected
 ///
 /// # Errors
 ///
-/// Returns error if market is already resolved, outcome index is invalid
+/// Returns error if market is already resolved, the program or market is paused, trading is closed,
+/// outcome index is invalid, trade has expired or the cost exceeds `max_cost`
 pub fn buy_outcome(
     ctx: Context<BuySellOutcome>,
     outcome_idx: u8,
-    num_shares: u64
+    num_shares: u64,
+    max_cost: u64,
+    expires_at: Option<u64>
 ) -> Result<()> {
     require!(!ctx.accounts.market.is_resolved, CustomError::MarketAlreadyResolved);
-    require!(outcome_idx == 0 || outcome_idx == 1, CustomError::InvalidOutcome);
-
-    let const_before = calculate_cost(&ctx);
-
-    if outcome_idx == 0 {
-        ctx.accounts.market.num_outcome_0 += num_shares;
-    } else {
-        ctx.accounts.market.num_outcome_1 += num_shares;
-    }
+    check_not_paused(&ctx.accounts.config, &ctx.accounts.market)?;
+    require!(outcome_idx < ctx.accounts.market.num_outcomes, CustomError::InvalidOutcome);
+    check_trading_window(&ctx.accounts.market, Clock::get()?.unix_timestamp)?;
+    check_expiry(expires_at)?;
+
+    let market = &ctx.accounts.market;
+    let cost_in_tokens = buy_cost(market, outcome_idx, num_shares, ctx.accounts.subsidy_mint.decimals)?;
+    let fees = trade_fees(cost_in_tokens, ctx.accounts.config.protocol_fee_bps, market.creator_fee_bps)?;
+    require!(
+        buy_debit(&ctx.accounts.subsidy_mint, cost_in_tokens, fees)? <= max_cost,
+        CustomError::SlippageExceeded
+    );
+
+    execute_buy(ctx, outcome_idx, num_shares, cost_in_tokens, fees)
+}
 
-    let cost_after = calculate_cost(&ctx);
-
-    let cost_to_buy = cost_after - const_before;
-    let cost_in_tokens = (cost_to_buy * 10u64.pow(ctx.accounts.subsidy_mint.decimals as u32) as f64) as u64;
-    
-    // Transfer the amount of subsidy tokens to the program
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_accounts = Transfer {
-        from: ctx.accounts.signer_token_account.to_account_info(),
-        to: ctx.accounts.program_token_account.to_account_info(),
-        authority: ctx.accounts.signer.to_account_info(),
+/// Buy as many outcome shares as a given amount of tokens pays for
+///
+/// Inverts the LMSR cost function to find the largest number of shares
+/// whose cost including trading and transfer fees does not exceed `collateral_amount`.
+/// Only the actual cost and fees of those shares are transferred from the trader.
+///
+/// # Arguments
+///
+/// * `ctx` - BuySellOutcome context containing required accounts
+/// * `outcome_idx` - Index of outcome to buy (less than the market's number of outcomes)
+/// * `collateral_amount` - Maximum amount of tokens (with decimals) debited from the trader, including
+///   trading fees and any transfer fee of a Token-2022 mint
+/// * `min_shares` - Minimum number of outcome shares the trader is willing to receive
+/// * `expires_at` - Optional unix timestamp (in seconds) after which the trade is rejected
+///
+/// # Errors
+///
+/// Returns error if market is already resolved, the program or market is paused, trading is closed,
+/// outcome index is invalid, trade has expired or fewer than `min_shares` shares can be bought
+pub fn buy_outcome_exact_in(
+    ctx: Context<BuySellOutcome>,
+    outcome_idx: u8,
+    collateral_amount: u64,
+    min_shares: u64,
+    expires_at: Option<u64>
+) -> Result<()> {
+    require!(!ctx.accounts.market.is_resolved, CustomError::MarketAlreadyResolved);
+    check_not_paused(&ctx.accounts.config, &ctx.accounts.market)?;
+    require!(outcome_idx < ctx.accounts.market.num_outcomes, CustomError::InvalidOutcome);
+    check_trading_window(&ctx.accounts.market, Clock::get()?.unix_timestamp)?;
+    check_expiry(expires_at)?;
+
+    let market = &ctx.accounts.market;
+    let mint = &ctx.accounts.subsidy_mint;
+    let decimals = mint.decimals;
+    let protocol_fee_bps = ctx.accounts.config.protocol_fee_bps;
+    let cost_and_fees = |num_shares: u64| -> Result<(u64, (u64, u64))> {
+        let cost = buy_cost(market, outcome_idx, num_shares, decimals)?;
+        Ok((cost, trade_fees(cost, protocol_fee_bps, market.creator_fee_bps)?))
     };
-    transfer(CpiContext::new(cpi_program, cpi_accounts), cost_in_tokens)?;
-
-    // Store adjusted prices according to LMSR formula
-    let (price_0, price_1) = get_prices(&ctx);
-    ctx.accounts.market.price_outcome_0 = price_0;
-    ctx.accounts.market.price_outcome_1 = price_1;
-
-    // Update the balance of the market
-    ctx.accounts.market.current_balance += cost_in_tokens;
 
-    // Update user outcomes shares
-    if outcome_idx == 0 {
-        ctx.accounts.outcome_account.amount_0 += num_shares;
-        ctx.accounts.market.num_outcome_0_held += num_shares;
-    } else {
-        ctx.accounts.outcome_account.amount_1 += num_shares;
-        ctx.accounts.market.num_outcome_1_held += num_shares;
-    }
+    // Spend the collateral left after its transfer fee, then buy fewer shares if rounding
+    // the transfer fee of each transfer up still debits more than `collateral_amount`
+    let budget = received_amount(mint, collateral_amount)?;
+    let estimate = shares_for_collateral(market, outcome_idx, budget, decimals, protocol_fee_bps)?;
+    let exceeds_collateral = |num_shares: u64| -> Result<bool> {
+        let (cost, fees) = cost_and_fees(num_shares)?;
+        Ok(buy_debit(mint, cost, fees)? > collateral_amount)
+    };
+    let num_shares = search_shares(estimate, estimate, exceeds_collateral)?.map_or(estimate, |n| n.saturating_sub(1));
+    require!(num_shares > 0 && num_shares >= min_shares, CustomError::SlippageExceeded);
 
-    Ok(())
+    let (cost_in_tokens, fees) = cost_and_fees(num_shares)?;
+    execute_buy(ctx, outcome_idx, num_shares, cost_in_tokens, fees)
 }
 
 /// Sell outcome shares back to the market
 ///
 /// Uses LMSR (Logarithmic Market Scoring Rule) to calculate sale price
-/// and executes token transfers between program and user accounts
+/// and executes token transfers between program and user accounts.
+/// The proceeds are rounded down in favour of the market, and the protocol
+/// and creator fees are deducted from them. With a Token-2022 mint that
+/// charges a transfer fee, the fees are grossed up so the fee vaults receive
+/// them in full, and the trader pays the transfer fees.
 ///
 /// # Arguments
 ///
 /// * `ctx` - BuySellOutcome context containing required accounts
-/// * `outcome_idx` - Index of outcome to sell (0 or 1)
+/// * `outcome_idx` - Index of outcome to sell (less than the market's number of outcomes)
 /// * `num_shares` - Number of outcome share to sell
+/// * `min_proceeds` - Minimum amount of tokens (with decimals) the trader is willing to receive, after trading fees
+///   and any transfer fee of a Token-2022 mint
+/// * `expires_at` - Optional unix timestamp (in seconds) after which the trade is rejected
 ///
 /// # Errors
 ///
-/// Returns error if market is already resolved, outcome index is invalid,
+/// Returns error if market is already resolved, the program or market is paused, trading is closed,
+/// outcome index is invalid, the position holds fewer than `num_shares` shares, trade has expired
+/// or the proceeds are below `min_proceeds`
 pub fn sell_outcome(
     ctx: Context<BuySellOutcome>,
     outcome_idx: u8,
-    num_shares: u64
+    num_shares: u64,
+    min_proceeds: u64,
+    expires_at: Option<u64>
 ) -> Result<()> {
     require!(!ctx.accounts.market.is_resolved, CustomError::MarketAlreadyResolved);
-    require!(outcome_idx == 0 || outcome_idx == 1, CustomError::InvalidOutcome);
-    
-    let const_before = calculate_cost(&ctx);
+    check_not_paused(&ctx.accounts.config, &ctx.accounts.market)?;
+    require!(outcome_idx < ctx.accounts.market.num_outcomes, CustomError::InvalidOutcome);
+    check_trading_window(&ctx.accounts.market, Clock::get()?.unix_timestamp)?;
+    check_expiry(expires_at)?;
+
+    require!(
+        num_shares <= shares_owned(&ctx.accounts.outcome_account, outcome_idx),
+        CustomError::InsufficientShares
+    );
+
+    let market = &ctx.accounts.market;
+    let proceeds = sell_proceeds(market, outcome_idx, num_shares, ctx.accounts.subsidy_mint.decimals)?;
+    let fees = trade_fees(proceeds, ctx.accounts.config.protocol_fee_bps, market.creator_fee_bps)?;
+    let mint = &ctx.accounts.subsidy_mint;
+    require!(
+        received_amount(mint, sell_payout(mint, proceeds, fees)?)? >= min_proceeds,
+        CustomError::SlippageExceeded
+    );
+
+    execute_sell(ctx, outcome_idx, num_shares, proceeds, fees)
+}
 
-    if outcome_idx == 0 {
-        ctx.accounts.market.num_outcome_0 -= num_shares;
-    } else {
-        ctx.accounts.market.num_outcome_1 -= num_shares;
+/// Sell the fewest outcome shares that pay out a given amount of tokens
+///
+/// Inverts the LMSR cost function to find the smallest number of shares
+/// whose proceeds after trading and transfer fees are at least `proceeds_amount`.
+/// The full proceeds of those shares, minus fees, are transferred to the trader.
+///
+/// # Arguments
+///
+/// * `ctx` - BuySellOutcome context containing required accounts
+/// * `outcome_idx` - Index of outcome to sell (less than the market's number of outcomes)
+/// * `proceeds_amount` - Amount of tokens (with decimals) to receive, after trading fees and any transfer fee
+///   of a Token-2022 mint
+/// * `max_shares` - Maximum number of outcome shares the trader is willing to sell
+/// * `expires_at` - Optional unix timestamp (in seconds) after which the trade is rejected
+///
+/// # Errors
+///
+/// Returns error if market is already resolved, the program or market is paused, trading is closed,
+/// outcome index is invalid, trade has expired, more than `max_shares` shares are needed
+/// or the position holds fewer shares than needed
+pub fn sell_outcome_exact_out(
+    ctx: Context<BuySellOutcome>,
+    outcome_idx: u8,
+    proceeds_amount: u64,
+    max_shares: u64,
+    expires_at: Option<u64>
+) -> Result<()> {
+    require!(!ctx.accounts.market.is_resolved, CustomError::MarketAlreadyResolved);
+    check_not_paused(&ctx.accounts.config, &ctx.accounts.market)?;
+    require!(outcome_idx < ctx.accounts.market.num_outcomes, CustomError::InvalidOutcome);
+    check_trading_window(&ctx.accounts.market, Clock::get()?.unix_timestamp)?;
+    check_expiry(expires_at)?;
+
+    let market = &ctx.accounts.market;
+    let mint = &ctx.accounts.subsidy_mint;
+    let decimals = mint.decimals;
+    let protocol_fee_bps = ctx.accounts.config.protocol_fee_bps;
+    let proceeds_and_fees = |num_shares: u64| -> Result<(u64, (u64, u64))> {
+        let proceeds = sell_proceeds(market, outcome_idx, num_shares, decimals)?;
+        Ok((proceeds, trade_fees(proceeds, protocol_fee_bps, market.creator_fee_bps)?))
+    };
+    let amount_received = |num_shares: u64| -> Result<u64> {
+        let (proceeds, fees) = proceeds_and_fees(num_shares)?;
+        received_amount(mint, sell_payout(mint, proceeds, fees)?)
+    };
+
+    // Sell for proceeds that also cover the transfer fee to the trader and, estimated at
+    // those proceeds, the transfer fees of the trading fees, then search the fewest shares
+    // the trader receives at least `proceeds_amount` for
+    let target_amount = gross_amount(mint, proceeds_amount)?;
+    let mut estimate = shares_for_proceeds(market, outcome_idx, target_amount, decimals, protocol_fee_bps)?;
+    let fees_transfer_fee = fees_transfer_fee(mint, proceeds_and_fees(estimate)?.1)?;
+    if fees_transfer_fee > 0 {
+        let target_amount = target_amount.checked_add(fees_transfer_fee).ok_or(CustomError::MathOverflow)?;
+        estimate = shares_for_proceeds(market, outcome_idx, target_amount, decimals, protocol_fee_bps)?;
+    }
+    // Proceeds too small to pay for the fees fall short of any amount
+    let pays_out = |num_shares: u64| -> Result<bool> {
+        Ok(amount_received(num_shares).is_ok_and(|amount| amount >= proceeds_amount))
+    };
+    let num_shares = search_shares(estimate, market.quantities[outcome_idx as usize], pays_out)?
+        .ok_or(CustomError::InsufficientOutcomeAvailable)?;
+    require!(num_shares <= max_shares, CustomError::SlippageExceeded);
+    require!(
+        num_shares <= shares_owned(&ctx.accounts.outcome_account, outcome_idx),
+        CustomError::InsufficientShares
+    );
+
+    let (proceeds, fees) = proceeds_and_fees(num_shares)?;
+    execute_sell(ctx, outcome_idx, num_shares, proceeds, fees)
+}
+
+/// Transfer the cost and fees of a buy from the trader and credit the bought shares
+///
+/// # Arguments
+///
+/// * `ctx` - BuySellOutcome context containing required accounts
+/// * `outcome_idx` - Index of outcome to buy (less than the market's number of outcomes)
+/// * `num_shares` - Number of outcome shares to purchase
+/// * `cost_in_tokens` - Cost of the shares in tokens (with decimals)
+/// * `fees` - Tuple of (protocol_fee, creator_fee) in tokens (with decimals)
+fn execute_buy(
+    ctx: Context<BuySellOutcome>,
+    outcome_idx: u8,
+    num_shares: u64,
+    cost_in_tokens: u64,
+    fees: (u64, u64)
+) -> Result<()> {
+    // Transfer the amount of subsidy tokens to the program and the fees to their vaults,
+    // grossed up by the transfer fee of the mint, if any, so each vault receives them in full
+    let (protocol_fee, creator_fee) = fees;
+    let vault_balance_before = ctx.accounts.market_vault.amount;
+    for (to, amount) in [
+        (&ctx.accounts.market_vault, cost_in_tokens),
+        (&ctx.accounts.protocol_fee_vault, protocol_fee),
+        (&ctx.accounts.creator_fee_vault, creator_fee),
+    ] {
+        if amount == 0 {
+            continue;
+        }
+        transfer_tokens(
+            &ctx.accounts.token_program,
+            &ctx.accounts.signer_token_account,
+            to,
+            &ctx.accounts.signer.to_account_info(),
+            &ctx.accounts.subsidy_mint,
+            gross_amount(&ctx.accounts.subsidy_mint, amount)?,
+            &[],
+        )?;
     }
+    ctx.accounts.market_vault.reload()?;
+    let amount_received = ctx
+        .accounts
+        .market_vault
+        .amount
+        .checked_sub(vault_balance_before)
+        .ok_or(CustomError::MathOverflow)?;
+
+    let idx = outcome_idx as usize;
+    let market = &mut ctx.accounts.market;
+    market.quantities[idx] = market.quantities[idx].checked_add(num_shares).ok_or(CustomError::MathOverflow)?;
 
-    let cost_after = calculate_cost(&ctx);
+    // Store adjusted prices according to LMSR formula
+    market.prices = get_prices(market)?;
 
-    let cost_to_buy = cost_after - const_before;
-    let cost_in_tokens = (cost_to_buy * 10u64.pow(ctx.accounts.subsidy_mint.decimals as u32) as f64) as u64;
+    // Update the balance and accrued fees of the market
+    market.current_balance = market.current_balance.checked_add(amount_received).ok_or(CustomError::MathOverflow)?;
+    market.protocol_fees_accrued =
+        market.protocol_fees_accrued.checked_add(protocol_fee).ok_or(CustomError::MathOverflow)?;
+    market.creator_fees_accrued = market.creator_fees_accrued.checked_add(creator_fee).ok_or(CustomError::MathOverflow)?;
 
-    let signer_seeds: &[&[&[u8]]] = &[&[b"token", &[ctx.bumps.program_token_account]]];
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_accounts = Transfer {
-        from: ctx.accounts.program_token_account.to_account_info(),
-        to: ctx.accounts.signer_token_account.to_account_info(),
-        authority: ctx.accounts.program_token_account.to_account_info(),
-    };
-    transfer(CpiContext::new(cpi_program, cpi_accounts).with_signer(signer_seeds), cost_in_tokens)?;
+    // Update user outcomes shares
+    let outcome_account = &mut ctx.accounts.outcome_account;
+    if outcome_account.amounts.is_empty() {
+        outcome_account.amounts = vec![0; market.num_outcomes as usize];
+    }
+    outcome_account.amounts[idx] = outcome_account.amounts[idx].checked_add(num_shares).ok_or(CustomError::MathOverflow)?;
+    market.shares_held[idx] = market.shares_held[idx].checked_add(num_shares).ok_or(CustomError::MathOverflow)?;
+
+    // Track the collateral paid into the market for refunds of a voided market
+    outcome_account.cost_basis = outcome_account.cost_basis.checked_add(amount_received).ok_or(CustomError::MathOverflow)?;
+    market.total_cost_basis = market.total_cost_basis.checked_add(amount_received).ok_or(CustomError::MathOverflow)?;
+
+    emit_cpi!(Trade {
+        market: ctx.accounts.market.key(),
+        trader: ctx.accounts.signer.key(),
+        side: TradeSide::Buy,
+        outcome: outcome_idx,
+        shares: num_shares,
+        cost: cost_in_tokens,
+        protocol_fee,
+        creator_fee,
+        prices: ctx.accounts.market.prices.clone(),
+    });
+
+    Ok(())
+}
+
+/// Transfer the proceeds of a sell, minus fees, to the trader and debit the sold shares
+///
+/// # Arguments
+///
+/// * `ctx` - BuySellOutcome context containing required accounts
+/// * `outcome_idx` - Index of outcome to sell (less than the market's number of outcomes)
+/// * `num_shares` - Number of outcome shares to sell
+/// * `proceeds` - Proceeds of the shares in tokens (with decimals), before fees
+/// * `fees` - Tuple of (protocol_fee, creator_fee) in tokens (with decimals)
+fn execute_sell(
+    ctx: Context<BuySellOutcome>,
+    outcome_idx: u8,
+    num_shares: u64,
+    proceeds: u64,
+    fees: (u64, u64)
+) -> Result<()> {
+    // Transfer the fees to their vaults, grossed up by the transfer fee of the mint, if any,
+    // so each vault receives them in full, and the rest of the proceeds to the trader
+    let (protocol_fee, creator_fee) = fees;
+    let payout = sell_payout(&ctx.accounts.subsidy_mint, proceeds, fees)?;
+    let market_key = ctx.accounts.market.key();
+    let signer_seeds: &[&[&[u8]]] = &[&[b"vault", market_key.as_ref(), &[ctx.bumps.market_vault]]];
+    for (to, amount) in [
+        (&ctx.accounts.signer_token_account, payout),
+        (&ctx.accounts.protocol_fee_vault, gross_amount(&ctx.accounts.subsidy_mint, protocol_fee)?),
+        (&ctx.accounts.creator_fee_vault, gross_amount(&ctx.accounts.subsidy_mint, creator_fee)?),
+    ] {
+        if amount == 0 {
+            continue;
+        }
+        transfer_tokens(
+            &ctx.accounts.token_program,
+            &ctx.accounts.market_vault,
+            to,
+            &ctx.accounts.market_vault.to_account_info(),
+            &ctx.accounts.subsidy_mint,
+            amount,
+            signer_seeds,
+        )?;
+    }
+
+    let idx = outcome_idx as usize;
+    let market = &mut ctx.accounts.market;
+    market.quantities[idx] =
+        market.quantities[idx].checked_sub(num_shares).ok_or(CustomError::InsufficientOutcomeAvailable)?;
 
     // Store adjusted prices according to LMSR formula
-    let (price_0, price_1) = get_prices(&ctx);
-    ctx.accounts.market.price_outcome_0 = price_0;
-    ctx.accounts.market.price_outcome_1 = price_1;
+    market.prices = get_prices(market)?;
 
-    // Update the balance of the market
-    ctx.accounts.market.current_balance -= cost_in_tokens;
+    // Update the balance and accrued fees of the market
+    market.current_balance = market.current_balance.checked_sub(proceeds).ok_or(CustomError::MathOverflow)?;
+    market.protocol_fees_accrued =
+        market.protocol_fees_accrued.checked_add(protocol_fee).ok_or(CustomError::MathOverflow)?;
+    market.creator_fees_accrued = market.creator_fees_accrued.checked_add(creator_fee).ok_or(CustomError::MathOverflow)?;
 
     // Update user outcomes shares
-    if outcome_idx == 0 {
-        ctx.accounts.outcome_account.amount_0 -= num_shares;
-        ctx.accounts.market.num_outcome_0_held -= num_shares;
-    } else {
-        ctx.accounts.outcome_account.amount_1 -= num_shares;
-        ctx.accounts.market.num_outcome_1_held -= num_shares;
-    }
+    let outcome_account = &mut ctx.accounts.outcome_account;
+    let shares_owned = outcome_account.amounts.get_mut(idx).ok_or(CustomError::InsufficientShares)?;
+    *shares_owned = shares_owned.checked_sub(num_shares).ok_or(CustomError::InsufficientShares)?;
+    market.shares_held[idx] = market.shares_held[idx].checked_sub(num_shares).ok_or(CustomError::MathOverflow)?;
+
+    // Proceeds taken out of the market reduce the cost basis, down to zero
+    let cost_basis_reduction = proceeds.min(outcome_account.cost_basis);
+    outcome_account.cost_basis -= cost_basis_reduction;
+    market.total_cost_basis =
+        market.total_cost_basis.checked_sub(cost_basis_reduction).ok_or(CustomError::MathOverflow)?;
+
+    emit_cpi!(Trade {
+        market: ctx.accounts.market.key(),
+        trader: ctx.accounts.signer.key(),
+        side: TradeSide::Sell,
+        outcome: outcome_idx,
+        shares: num_shares,
+        cost: proceeds,
+        protocol_fee,
+        creator_fee,
+        prices: ctx.accounts.market.prices.clone(),
+    });
 
     Ok(())
 }
 
-/// Calculate the cost function for the market using LMSR
+/// Check that a trade is submitted within the trading window of the market
+///
+/// # Arguments
 ///
-/// Implements C(q) = b * ln(exp(q_0/b) + exp(q_1/b))
-/// where q_0 and q_1 are the quantities of outcome tokens
-/// and b is the liquidity constant
+/// * `market` - Market to trade on
+/// * `now` - Current unix timestamp (in seconds)
+///
+/// # Errors
+///
+/// Returns error if trading has not opened yet or has already closed
+pub fn check_trading_window(market: &Market, now: i64) -> Result<()> {
+    let now = u64::try_from(now).map_err(|_| CustomError::MathOverflow)?;
+    require!(now >= market.trading_opens_at, CustomError::TradingNotOpen);
+    require!(now < market.trading_closes_at, CustomError::TradingClosed);
+    Ok(())
+}
+
+/// Check that a trade is submitted before its expiry timestamp, if any
 ///
 /// # Arguments
 ///
-/// * `ctx` - BuySellOutcome context containing market state
+/// * `expires_at` - Optional unix timestamp (in seconds) after which the trade is rejected
+pub fn check_expiry(expires_at: Option<u64>) -> Result<()> {
+    if let Some(expires_at) = expires_at {
+        let now = u64::try_from(Clock::get()?.unix_timestamp).map_err(|_| CustomError::MathOverflow)?;
+        require!(now <= expires_at, CustomError::TradeExpired);
+    }
+    Ok(())
+}
+
+/// Calculate the protocol and creator fees charged on a trade
+///
+/// Both fees are rounded up in favour of the fee recipients.
+///
+/// # Arguments
+///
+/// * `amount` - Cost or proceeds of the trade in tokens (with decimals)
+/// * `protocol_fee_bps` - Protocol fee in basis points
+/// * `creator_fee_bps` - Creator fee in basis points
+///
+/// # Returns
+///
+/// Tuple of (protocol_fee, creator_fee) in tokens (with decimals)
+pub fn trade_fees(amount: u64, protocol_fee_bps: u16, creator_fee_bps: u16) -> Result<(u64, u64)> {
+    let fee = |bps: u16| {
+        u64::try_from((amount as u128 * bps as u128).div_ceil(BPS_DENOMINATOR as u128))
+            .map_err(|_| error!(CustomError::MathOverflow))
+    };
+
+    Ok((fee(protocol_fee_bps)?, fee(creator_fee_bps)?))
+}
+
+/// Calculate the amount debited from the trader for a buy, including fees
+///
+/// Each transfer of `execute_buy` is grossed up by the transfer fee of the mint, if any.
+///
+/// # Arguments
+///
+/// * `mint` - Mint of the market's collateral
+/// * `cost` - Cost of the trade in tokens (with decimals)
+/// * `fees` - Tuple of (protocol_fee, creator_fee) in tokens (with decimals)
+pub fn buy_debit(mint: &InterfaceAccount<Mint>, cost: u64, fees: (u64, u64)) -> Result<u64> {
+    [cost, fees.0, fees.1].into_iter().filter(|amount| *amount > 0).try_fold(0u64, |debit, amount| {
+        Ok(debit.checked_add(gross_amount(mint, amount)?).ok_or(CustomError::MathOverflow)?)
+    })
+}
+
+/// Calculate the transfer fees of sending the trading fees of a sell in full to their vaults
+///
+/// # Arguments
+///
+/// * `mint` - Mint of the market's collateral
+/// * `fees` - Tuple of (protocol_fee, creator_fee) in tokens (with decimals)
+fn fees_transfer_fee(mint: &InterfaceAccount<Mint>, fees: (u64, u64)) -> Result<u64> {
+    let transfer_fee = |fee: u64| -> Result<u64> { Ok(gross_amount(mint, fee)? - fee) };
+    Ok(transfer_fee(fees.0)?.checked_add(transfer_fee(fees.1)?).ok_or(CustomError::MathOverflow)?)
+}
+
+/// Calculate the amount transferred from the market vault to the trader for a sell
+///
+/// The proceeds are reduced by the fees and by the transfer fees of sending them to their vaults.
+///
+/// # Arguments
+///
+/// * `mint` - Mint of the market's collateral
+/// * `proceeds` - Proceeds of the trade in tokens (with decimals)
+/// * `fees` - Tuple of (protocol_fee, creator_fee) in tokens (with decimals)
+///
+/// # Errors
+///
+/// Returns error if the proceeds are too small to pay for the fees
+pub fn sell_payout(mint: &InterfaceAccount<Mint>, proceeds: u64, fees: (u64, u64)) -> Result<u64> {
+    let fees_transfer_fee = fees_transfer_fee(mint, fees)?;
+    Ok(fees
+        .0
+        .checked_add(fees.1)
+        .and_then(|total_fees| total_fees.checked_add(fees_transfer_fee))
+        .and_then(|total_fees| proceeds.checked_sub(total_fees))
+        .ok_or(CustomError::MathOverflow)?)
+}
+
+/// Calculate the amount paid by the trader for a buy, including fees
+///
+/// # Arguments
+///
+/// * `cost` - Cost of the trade in tokens (with decimals)
+/// * `fees` - Tuple of (protocol_fee, creator_fee) in tokens (with decimals)
+fn total_amount(cost: u64, fees: (u64, u64)) -> Result<u64> {
+    Ok(cost
+        .checked_add(fees.0)
+        .and_then(|amount| amount.checked_add(fees.1))
+        .ok_or(CustomError::MathOverflow)?)
+}
+
+/// Calculate the amount received by the trader for a sell, after fees
+///
+/// Proceeds too small to pay for the fees result in zero.
+///
+/// # Arguments
+///
+/// * `proceeds` - Proceeds of the trade in tokens (with decimals)
+/// * `fees` - Tuple of (protocol_fee, creator_fee) in tokens (with decimals)
+fn net_amount(proceeds: u64, fees: (u64, u64)) -> Result<u64> {
+    let total_fees = fees.0.checked_add(fees.1).ok_or(CustomError::MathOverflow)?;
+    Ok(proceeds.saturating_sub(total_fees))
+}
+
+/// Number of shares of an outcome held by a position
+///
+/// # Arguments
+///
+/// * `outcome_account` - Position of the trader
+/// * `outcome_idx` - Index of the outcome
+pub fn shares_owned(outcome_account: &OutcomeAccount, outcome_idx: u8) -> u64 {
+    outcome_account.amounts.get(outcome_idx as usize).copied().unwrap_or_default()
+}
+
+/// Calculate the cost of buying shares of an outcome using LMSR
+///
+/// Implements C(q') - C(q) where C(q) = b * ln(sum_i(exp(q_i/b))),
+/// q_i are the quantities of outcome tokens, q' the quantities
+/// after the trade and b is the liquidity parameter of the market.
+/// The cost is rounded up.
+///
+/// # Arguments
+///
+/// * `market` - Market to trade on
+/// * `outcome_idx` - Index of outcome to buy (less than the market's number of outcomes)
+/// * `num_shares` - Number of outcome shares to buy
+/// * `decimals` - Decimals of the subsidy mint
+///
+/// # Returns
+///
+/// The cost in tokens (with decimals)
+pub fn buy_cost(market: &Market, outcome_idx: u8, num_shares: u64, decimals: u8) -> Result<u64> {
+    if num_shares == 0 {
+        return Ok(0);
+    }
+
+    let mut quantities = market.quantities.clone();
+    let cost_before = lmsr_cost(&quantities, market.liquidity_b, Rounding::Down)?;
+
+    let idx = outcome_idx as usize;
+    quantities[idx] = quantities[idx].checked_add(num_shares).ok_or(CustomError::MathOverflow)?;
+    let cost_after = lmsr_cost(&quantities, market.liquidity_b, Rounding::Up)?;
+
+    let cost_to_buy = cost_after.checked_sub(cost_before).ok_or(CustomError::MathOverflow)?;
+    to_token_amount(cost_to_buy, decimals, Rounding::Up)
+}
+
+/// Calculate the proceeds of selling shares of an outcome using LMSR
+///
+/// Implements C(q) - C(q') with the same notation as `buy_cost`.
+/// The proceeds are rounded down.
+///
+/// # Arguments
+///
+/// * `market` - Market to trade on
+/// * `outcome_idx` - Index of outcome to sell (less than the market's number of outcomes)
+/// * `num_shares` - Number of outcome shares to sell
+/// * `decimals` - Decimals of the subsidy mint
+///
+/// # Returns
+///
+/// The proceeds in tokens (with decimals)
+pub fn sell_proceeds(market: &Market, outcome_idx: u8, num_shares: u64, decimals: u8) -> Result<u64> {
+    let mut quantities = market.quantities.clone();
+    let cost_before = lmsr_cost(&quantities, market.liquidity_b, Rounding::Down)?;
+
+    let idx = outcome_idx as usize;
+    quantities[idx] = quantities[idx].checked_sub(num_shares).ok_or(CustomError::InsufficientOutcomeAvailable)?;
+    let cost_after = lmsr_cost(&quantities, market.liquidity_b, Rounding::Up)?;
+
+    to_token_amount(cost_before.saturating_sub(cost_after), decimals, Rounding::Down)
+}
+
+/// Find the largest number of shares of an outcome that costs at most `collateral_amount`, including fees
+///
+/// The closed-form inverse of the cost function gives an estimate that
+/// `search_shares` then corrects against `buy_cost` and `trade_fees`, so the
+/// result is exact w.r.t. the rounding used when buying.
+///
+/// # Arguments
+///
+/// * `market` - Market to trade on
+/// * `outcome_idx` - Index of outcome to buy (less than the market's number of outcomes)
+/// * `collateral_amount` - Maximum amount of tokens (with decimals) to spend, including trading fees
+///   but excluding any transfer fee of a Token-2022 mint
+/// * `decimals` - Decimals of the subsidy mint
+/// * `protocol_fee_bps` - Protocol fee in basis points
+pub fn shares_for_collateral(
+    market: &Market,
+    outcome_idx: u8,
+    collateral_amount: u64,
+    decimals: u8,
+    protocol_fee_bps: u16
+) -> Result<u64> {
+    let total_cost = |num_shares: u64| -> Result<u64> {
+        let cost = buy_cost(market, outcome_idx, num_shares, decimals)?;
+        total_amount(cost, trade_fees(cost, protocol_fee_bps, market.creator_fee_bps)?)
+    };
+
+    // Estimate the shares with the collateral that is left after fees
+    let fee_bps = protocol_fee_bps as u64 + market.creator_fee_bps as u64;
+    let budget = (collateral_amount as u128 * BPS_DENOMINATOR as u128 / (BPS_DENOMINATOR + fee_bps) as u128) as u64;
+    let quantities = &market.quantities;
+    let idx = outcome_idx as usize;
+    let target_cost = lmsr_cost(quantities, market.liquidity_b, Rounding::Down)?
+        .checked_add(from_token_amount(budget, decimals)?)
+        .ok_or(CustomError::MathOverflow)?;
+
+    let estimate = lmsr_quantity_for_cost(quantities, idx, market.liquidity_b, target_cost)
+        .map(|quantity| u64::try_from(quantity / SCALE).unwrap_or(u64::MAX).saturating_sub(quantities[idx]))
+        .unwrap_or_default();
+
+    // Shares whose cost overflows cost more than any collateral amount
+    let exceeds_collateral =
+        |num_shares: u64| -> Result<bool> { Ok(!total_cost(num_shares).is_ok_and(|cost| cost <= collateral_amount)) };
+    Ok(search_shares(estimate, u64::MAX, exceeds_collateral)?.map_or(u64::MAX, |n| n.saturating_sub(1)))
+}
+
+/// Find the smallest number of shares of an outcome whose proceeds after fees are at least `proceeds_amount`
+///
+/// The closed-form inverse of the cost function gives an estimate that
+/// `search_shares` then corrects against `sell_proceeds` and `trade_fees`, so
+/// the result is exact w.r.t. the rounding used when selling.
+///
+/// # Arguments
+///
+/// * `market` - Market to trade on
+/// * `outcome_idx` - Index of outcome to sell (less than the market's number of outcomes)
+/// * `proceeds_amount` - Amount of tokens (with decimals) to receive, after fees
+/// * `decimals` - Decimals of the subsidy mint
+/// * `protocol_fee_bps` - Protocol fee in basis points
+///
+/// # Errors
+///
+/// Returns error if the market does not hold enough shares to pay out `proceeds_amount`
+pub fn shares_for_proceeds(
+    market: &Market,
+    outcome_idx: u8,
+    proceeds_amount: u64,
+    decimals: u8,
+    protocol_fee_bps: u16
+) -> Result<u64> {
+    let net_proceeds = |num_shares: u64| -> Result<u64> {
+        let proceeds = sell_proceeds(market, outcome_idx, num_shares, decimals)?;
+        net_amount(proceeds, trade_fees(proceeds, protocol_fee_bps, market.creator_fee_bps)?)
+    };
+
+    // Estimate the shares with the proceeds needed before fees
+    let fee_bps = protocol_fee_bps as u64 + market.creator_fee_bps as u64;
+    let gross_amount = (proceeds_amount as u128 * BPS_DENOMINATOR as u128).div_ceil((BPS_DENOMINATOR - fee_bps) as u128);
+    let gross_amount = u64::try_from(gross_amount).map_err(|_| CustomError::MathOverflow)?;
+    let quantities = &market.quantities;
+    let idx = outcome_idx as usize;
+    let max_shares = quantities[idx];
+    let target_cost = lmsr_cost(quantities, market.liquidity_b, Rounding::Down)?
+        .checked_sub(from_token_amount(gross_amount, decimals)?)
+        .ok_or(CustomError::InsufficientOutcomeAvailable)?;
+
+    let estimate = lmsr_quantity_for_cost(quantities, idx, market.liquidity_b, target_cost)
+        .map(|quantity| max_shares.saturating_sub(u64::try_from(quantity / SCALE).unwrap_or(u64::MAX)))
+        .unwrap_or(max_shares);
+
+    let pays_out = |num_shares: u64| -> Result<bool> { Ok(net_proceeds(num_shares)? >= proceeds_amount) };
+    Ok(search_shares(estimate, max_shares, pays_out)?.ok_or(CustomError::InsufficientOutcomeAvailable)?)
+}
+
+/// Find the fewest shares within `[0, max_shares]` for which `reached` holds, starting from an estimate
+///
+/// `reached` must hold for every number of shares above one for which it holds, as the cost
+/// and proceeds of a trade grow with its shares. The range around the estimate is bracketed
+/// with steps doubling in size and then bisected, so `reached` is evaluated at most about
+/// 128 times however far off the estimate is.
+///
+/// # Arguments
+///
+/// * `estimate` - Estimated number of shares
+/// * `max_shares` - Largest number of shares to search
+/// * `reached` - Whether a number of shares reaches the target of the search
 ///
 /// # Returns
 ///
-/// The cost value according to the LMSR formula
-fn calculate_cost(ctx: &Context<BuySellOutcome>) -> f64 {
-    let b = LIQUIDITY_CONSTANT;
-    let exp_no = (ctx.accounts.market.num_outcome_1).checked_div(b).unwrap_or_default();
-    let exp_yes = (ctx.accounts.market.num_outcome_0).checked_div(b).unwrap_or_default();
+/// The fewest shares reaching the target, or `None` if `max_shares` does not reach it
+fn search_shares(estimate: u64, max_shares: u64, mut reached: impl FnMut(u64) -> Result<bool>) -> Result<Option<u64>> {
+    let estimate = estimate.min(max_shares) as u128;
+    // The target is first reached within `[low, high)`, `high` being past `max_shares` until it is known to reach it
+    let mut low = 0u128;
+    let mut high = max_shares as u128 + 1;
+    let mut step = 1u128;
+    if reached(estimate as u64)? {
+        high = estimate;
+        while high > 0 {
+            let candidate = estimate.saturating_sub(step);
+            if !reached(candidate as u64)? {
+                low = candidate + 1;
+                break;
+            }
+            high = candidate;
+            step *= 2;
+        }
+    } else {
+        low = estimate + 1;
+        while low < high {
+            let candidate = (estimate + step).min(max_shares as u128);
+            if reached(candidate as u64)? {
+                high = candidate;
+                break;
+            }
+            low = candidate + 1;
+            step *= 2;
+        }
+    }
 
-    // Approximate the natural log sum of exponentials
-    // C(q) = b * ln(exp(q_yes/b) + exp(q_no/b))
-    // For safety, we use the max exponential and add the difference
-    let max_exp = exp_yes.max(exp_no);
-    let diff = ((exp_yes - max_exp) as f64).exp() + ((exp_no - max_exp) as f64).exp();
-    
-    (b as f64) * (max_exp as f64 + diff.ln())
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if reached(mid as u64)? {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    Ok((low <= max_shares as u128).then_some(low as u64))
 }
 
 /// Calculate market prices for each outcome based on LMSR
@@ -217,20 +831,94 @@ fn calculate_cost(ctx: &Context<BuySellOutcome>) -> f64 {
 ///
 /// # Arguments
 ///
-/// * `ctx` - BuySellOutcome context containing market state
+/// * `market` - Market to calculate the prices for
 ///
 /// # Returns
 ///
-/// Tuple of (price_0, price_1) representing probabilities of each outcome
-fn get_prices(ctx: &Context<BuySellOutcome>) -> (f64, f64) {
-    let b = LIQUIDITY_CONSTANT as f64;
+/// Vector of prices representing probabilities of each outcome, scaled by `math::SCALE`.
+/// The last price absorbs the rounding so that the prices sum up to exactly one.
+pub fn get_prices(market: &Market) -> Result<Vec<u64>> {
+    let mut prices = lmsr_prices(&market.quantities, market.liquidity_b)?;
+    let (last, others) = prices.split_last_mut().ok_or(CustomError::InvalidOutcomeCount)?;
+    *last = (SCALE as u64).checked_sub(others.iter().sum::<u64>()).ok_or(CustomError::MathOverflow)?;
+
+    Ok(prices)
+}
 
-    let exp_0 = (ctx.accounts.market.num_outcome_0 as f64 / b).exp();
-    let exp_1 = (ctx.accounts.market.num_outcome_1 as f64 / b).exp();
-    let sum = exp_0 + exp_1;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::market::test_market;
+
+    #[test]
+    fn trades_only_within_the_trading_window() {
+        let mut market = test_market();
+        market.trading_opens_at = 100;
+        market.trading_closes_at = 1_000;
+
+        assert_eq!(check_trading_window(&market, 99).unwrap_err(), CustomError::TradingNotOpen.into());
+        assert!(check_trading_window(&market, 100).is_ok());
+        assert!(check_trading_window(&market, 999).is_ok());
+        // Trading closes at `trading_closes_at`, which defaults to the start of the resolve window
+        assert_eq!(check_trading_window(&market, 1_000).unwrap_err(), CustomError::TradingClosed.into());
+        assert_eq!(check_trading_window(&market, 1_500).unwrap_err(), CustomError::TradingClosed.into());
+    }
 
-    let price_0 = exp_0 / sum;
-    let price_1 = 1f64 - price_0;
+    #[test]
+    fn search_shares_is_bounded_however_far_off_the_estimate() {
+        for target in [0, 1, 7, 1_000, 123_456_789, u64::MAX] {
+            for estimate in [0, 1, target / 2, target, target.saturating_add(1), u64::MAX] {
+                let mut evaluations = 0;
+                let reached = |num_shares: u64| {
+                    evaluations += 1;
+                    Ok(num_shares >= target)
+                };
+                assert_eq!(search_shares(estimate, u64::MAX, reached).unwrap(), Some(target));
+                assert!(evaluations <= 130);
+            }
+        }
+
+        // Not reached within `max_shares`
+        assert_eq!(search_shares(5, 9, |num_shares| Ok(num_shares >= 10)).unwrap(), None);
+        assert_eq!(search_shares(50, 10, |num_shares| Ok(num_shares >= 10)).unwrap(), Some(10));
+    }
 
-    (price_0, price_1)
-}
\ No newline at end of file
+    #[test]
+    fn shares_for_collateral_and_proceeds_are_exact() {
+        let decimals = 6;
+        let protocol_fee_bps = 50;
+        let mut deep_market = test_market();
+        deep_market.liquidity_b = 1_000_000;
+        deep_market.quantities = vec![1_000_000, 1_000_000];
+        deep_market.creator_fee_bps = 100;
+
+        for market in [test_market(), deep_market] {
+            let total_cost = |num_shares: u64| {
+                let cost = buy_cost(&market, 0, num_shares, decimals).unwrap();
+                total_amount(cost, trade_fees(cost, protocol_fee_bps, market.creator_fee_bps).unwrap()).unwrap()
+            };
+            let net_proceeds = |num_shares: u64| {
+                let proceeds = sell_proceeds(&market, 0, num_shares, decimals).unwrap();
+                net_amount(proceeds, trade_fees(proceeds, protocol_fee_bps, market.creator_fee_bps).unwrap()).unwrap()
+            };
+
+            for amount in [1, 999, 1_000_000, 10_000_000, 25_000_000] {
+                // The most shares costing at most the collateral amount
+                let num_shares = shares_for_collateral(&market, 0, amount, decimals, protocol_fee_bps).unwrap();
+                assert!(total_cost(num_shares) <= amount);
+                assert!(total_cost(num_shares + 1) > amount);
+
+                // The fewest shares paying out at least the proceeds amount
+                let num_shares = shares_for_proceeds(&market, 0, amount, decimals, protocol_fee_bps).unwrap();
+                assert!(net_proceeds(num_shares) >= amount);
+                assert!(num_shares == 0 || net_proceeds(num_shares - 1) < amount);
+            }
+        }
+
+        // Selling every share of the outcome pays out less than the whole subsidy
+        assert_eq!(
+            shares_for_proceeds(&test_market(), 0, 100_000_000, decimals, protocol_fee_bps).unwrap_err(),
+            CustomError::InsufficientOutcomeAvailable.into()
+        );
+    }
+}
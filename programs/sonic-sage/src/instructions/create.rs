@@ -2,17 +2,30 @@ use {
     anchor_lang::prelude::*,
     anchor_spl::{
         associated_token::AssociatedToken,
-        token::{transfer, Mint, Token, TokenAccount, Transfer},
+        token_interface::{Mint, TokenAccount, TokenInterface},
     },
 };
 
+use pyth_solana_receiver_sdk::price_update::get_feed_id_from_hex;
+
 use crate::errors::*;
-use crate::state::market::Market;
+use crate::events::MarketCreated;
+use crate::math::{ln, rescale, Rounding, SCALE};
+use crate::state::collateral::CollateralMint;
+use crate::state::config::{Config, BPS_DENOMINATOR};
+use crate::instructions::buy_sell::get_prices;
+use crate::oracle::{check_oracle_kind, oracle_adapter};
+use crate::state::market::{
+    AmbiguousPriceAction, ConfidencePolicy, Market, MarketKind, OracleKind, OracleSource, PriceCondition,
+    SettlementMode, TwapAccumulator, MAX_LABEL_LEN, MAX_ORACLE_SOURCES, MAX_OUTCOMES, MAX_PRICE_EXPONENT,
+    MIN_PRICE_EXPONENT,
+};
 use crate::state::metadata::Metadata;
+use crate::transfer::{gross_amount, transfer_tokens};
 
 /// Context for creating a new market
+#[event_cpi]
 #[derive(Accounts)]
-#[instruction(price: u64, price_feed_id: String, resolve_from: u64, resolve_to: u64, subsidy_amount: u64)]
 pub struct CreateMarket<'info> {
     #[account(
         init,
@@ -26,91 +39,550 @@ pub struct CreateMarket<'info> {
     #[account(mut)]
     pub metadata: Account<'info, Metadata>,
 
+    #[account(
+        seeds = [b"config"],
+        bump,
+        constraint = !config.is_paused @ CustomError::ProgramPaused
+    )]
+    pub config: Account<'info, Config>,
+
     #[account(mut)]
-    pub mint: Account<'info, Mint>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"collateral", mint.key().as_ref()],
+        bump,
+        constraint = collateral_mint.is_enabled @ CustomError::CollateralMintNotAllowed
+    )]
+    pub collateral_mint: Account<'info, CollateralMint>,
 
     #[account(
         mut,
         token::mint = mint,
         token::authority = signer
     )]
-    pub signer_token_account: Account<'info, TokenAccount>,
+    pub signer_token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
-        mut,
-        seeds = [b"token"],
+        init,
+        payer = signer,
+        token::mint = mint,
+        token::authority = market_vault,
+        token::token_program = token_program,
+        seeds = [b"vault", market.key().as_ref()],
+        bump
+    )]
+    pub market_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = signer,
+        token::mint = mint,
+        token::authority = creator_fee_vault,
+        token::token_program = token_program,
+        seeds = [b"creator_fees", market.key().as_ref()],
         bump
     )]
-    pub program_token_account: Account<'info, TokenAccount>,
+    pub creator_fee_vault: InterfaceAccount<'info, TokenAccount>,
 
     #[account(mut)]
     pub signer: Signer<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
-/// Creates a new prediction market with initial liquidity subsidy
+/// Creates a new categorical prediction market with initial liquidity subsidy
 ///
 /// # Arguments
 ///
 /// * `ctx` - CreateMarket context containing required accounts
-/// * `price` - Predicted price for the market
-/// * `price_feed_id` - Identifier for pyth price feed used for resolution
-/// * `resolve_from` - Unix timestamp when the market can begin to be resolved
-/// * `resolve_to` - Unix timestamp deadline by which market must be resolved
+/// * `price_thresholds` - Ascending price thresholds, one less than the number of outcomes
+/// * `price_exponent` - Exponent of the price thresholds, a threshold `t` stands for the price `t * 10^price_exponent`
+/// * `condition` - Condition on the price deciding the winning outcome
+/// * `outcome_labels` - Human-readable label of every outcome
+/// * `oracle_kind` - Oracle provider of the price feed
+/// * `price_feed_id` - Hex encoded identifier of the price feed used for resolution
+/// * `resolve_from` - Unix timestamp in seconds when the market can begin to be resolved
+/// * `resolve_to` - Unix timestamp in seconds after which oracle prices are no longer accepted
 /// * `subsidy_amount` - Initial liquidity subsidy amount in whole tokens
+/// * `liquidity_b` - LMSR liquidity parameter of the market
+/// * `creator_fee_bps` - Fee charged by the creator on every trade, in basis points
+/// * `trading_opens_at` - Optional unix timestamp in seconds when trading opens, immediately if not set
+/// * `trading_closes_at` - Optional unix timestamp in seconds when trading closes, `resolve_from` if not set
+/// * `confidence_policy` - Policy applied to the confidence interval of the oracle price at resolution
+/// * `max_price_age` - Maximum age of the oracle price at resolution, in seconds
+/// * `allow_partial_verification` - Whether partially verified oracle price updates are accepted at resolution
+/// * `additional_oracles` - Price feeds of other oracle providers the market is also resolved from, at most two
+/// * `max_oracle_deviation_bps` - Maximum spread of the oracle prices, in basis points of their median
+/// * `settlement_mode` - Price the market is settled on, the spot, moving average or time-weighted average price
 ///
 /// # Errors
 ///
-/// Returns error if the resolve window is invalid, resolve time is in the past.
+/// Returns error if the program is paused, the collateral mint or price feed is not allowed,
+/// the mock oracle is used outside of localnet builds,
+/// the resolve window is invalid, resolve window starts in the past, the trading window,
+/// confidence policy, maximum price age, additional oracles or settlement mode are invalid,
+/// the number of outcomes is not between 2 and `MAX_OUTCOMES`, the thresholds are not
+/// ascending or do not match the condition, the price exponent is not between
+/// `MIN_PRICE_EXPONENT` and `MAX_PRICE_EXPONENT` or the thresholds cannot be rescaled
+/// to `MIN_PRICE_EXPONENT`, the outcome labels are invalid, liquidity
+/// parameter is zero, the subsidy does not cover the worst-case loss `b * ln(n)` or the
+/// creator fee is above the maximum creator fee of the configuration.
+#[allow(clippy::too_many_arguments)]
 pub fn create_market(
     ctx: Context<CreateMarket>,
-    price: f64,
+    price_thresholds: Vec<i64>,
+    price_exponent: i32,
+    condition: PriceCondition,
+    outcome_labels: Vec<String>,
+    oracle_kind: OracleKind,
+    price_feed_id: String,
+    resolve_from: u64,
+    resolve_to: u64,
+    subsidy_amount: u64,
+    liquidity_b: u64,
+    creator_fee_bps: u16,
+    trading_opens_at: Option<u64>,
+    trading_closes_at: Option<u64>,
+    confidence_policy: ConfidencePolicy,
+    max_price_age: u64,
+    allow_partial_verification: bool,
+    additional_oracles: Vec<OracleSource>,
+    max_oracle_deviation_bps: u16,
+    settlement_mode: SettlementMode
+) -> Result<()> {
+    let num_outcomes = match condition {
+        PriceCondition::Thresholds => price_thresholds.len() + 1,
+        PriceCondition::Inside => {
+            require!(price_thresholds.len() == 2, CustomError::InvalidPriceCondition);
+            2
+        }
+        PriceCondition::GreaterThan
+        | PriceCondition::GreaterOrEqual
+        | PriceCondition::LessThan
+        | PriceCondition::LessOrEqual => {
+            require!(price_thresholds.len() == 1, CustomError::InvalidPriceCondition);
+            2
+        }
+    };
+    require!((2..=MAX_OUTCOMES).contains(&num_outcomes), CustomError::InvalidOutcomeCount);
+    require!(
+        price_thresholds.windows(2).all(|pair| pair[0] < pair[1]),
+        CustomError::InvalidPriceThresholds
+    );
+    check_price_exponent(&price_thresholds, price_exponent)?;
+    require!(
+        outcome_labels.len() == num_outcomes
+            && outcome_labels.iter().all(|label| (1..=MAX_LABEL_LEN).contains(&label.len())),
+        CustomError::InvalidOutcomeLabels
+    );
+
+    ctx.accounts.market.price_thresholds = price_thresholds;
+    ctx.accounts.market.price_exponent = price_exponent;
+    ctx.accounts.market.condition = condition;
+    ctx.accounts.market.outcome_labels = outcome_labels;
+    initialize_market(
+        ctx,
+        MarketKind::Categorical,
+        num_outcomes,
+        oracle_kind,
+        price_feed_id,
+        resolve_from,
+        resolve_to,
+        subsidy_amount,
+        liquidity_b,
+        creator_fee_bps,
+        trading_opens_at,
+        trading_closes_at,
+        confidence_policy,
+        max_price_age,
+        allow_partial_verification,
+        additional_oracles,
+        max_oracle_deviation_bps,
+        settlement_mode,
+    )
+}
+
+/// Creates a new scalar prediction market with initial liquidity subsidy
+///
+/// The market trades LONG and SHORT shares. At resolution a LONG share pays
+/// `(clamp(price) - lower) / (upper - lower)` and a SHORT share the complement.
+///
+/// # Arguments
+///
+/// * `ctx` - CreateMarket context containing required accounts
+/// * `lower` - Lower bound of the price range, at or below which LONG shares pay nothing
+/// * `upper` - Upper bound of the price range, at or above which LONG shares pay in full
+/// * `price_exponent` - Exponent of the bounds, a bound `v` stands for the price `v * 10^price_exponent`
+/// * `oracle_kind` - Oracle provider of the price feed
+/// * `price_feed_id` - Hex encoded identifier of the price feed used for resolution
+/// * `resolve_from` - Unix timestamp in seconds when the market can begin to be resolved
+/// * `resolve_to` - Unix timestamp in seconds after which oracle prices are no longer accepted
+/// * `subsidy_amount` - Initial liquidity subsidy amount in whole tokens
+/// * `liquidity_b` - LMSR liquidity parameter of the market
+/// * `creator_fee_bps` - Fee charged by the creator on every trade, in basis points
+/// * `trading_opens_at` - Optional unix timestamp in seconds when trading opens, immediately if not set
+/// * `trading_closes_at` - Optional unix timestamp in seconds when trading closes, `resolve_from` if not set
+/// * `confidence_policy` - Policy applied to the confidence interval of the oracle price at resolution
+/// * `max_price_age` - Maximum age of the oracle price at resolution, in seconds
+/// * `allow_partial_verification` - Whether partially verified oracle price updates are accepted at resolution
+/// * `additional_oracles` - Price feeds of other oracle providers the market is also resolved from, at most two
+/// * `max_oracle_deviation_bps` - Maximum spread of the oracle prices, in basis points of their median
+/// * `settlement_mode` - Price the market is settled on, the spot, moving average or time-weighted average price
+///
+/// # Errors
+///
+/// Returns error if the program is paused, the collateral mint or price feed is not allowed,
+/// the mock oracle is used outside of localnet builds,
+/// the range is empty, the price exponent is not between `MIN_PRICE_EXPONENT` and
+/// `MAX_PRICE_EXPONENT` or the bounds cannot be rescaled to `MIN_PRICE_EXPONENT`, the resolve window is invalid, resolve window starts in the past,
+/// the trading window, confidence policy, maximum price age, additional oracles or settlement
+/// mode are invalid, liquidity parameter is zero, the subsidy does not cover the worst-case loss
+/// `b * ln(2)` or the creator fee is above the maximum creator fee of the configuration.
+#[allow(clippy::too_many_arguments)]
+pub fn create_scalar_market(
+    ctx: Context<CreateMarket>,
+    lower: i64,
+    upper: i64,
+    price_exponent: i32,
+    oracle_kind: OracleKind,
     price_feed_id: String,
     resolve_from: u64,
     resolve_to: u64,
-    subsidy_amount: u64
+    subsidy_amount: u64,
+    liquidity_b: u64,
+    creator_fee_bps: u16,
+    trading_opens_at: Option<u64>,
+    trading_closes_at: Option<u64>,
+    confidence_policy: ConfidencePolicy,
+    max_price_age: u64,
+    allow_partial_verification: bool,
+    additional_oracles: Vec<OracleSource>,
+    max_oracle_deviation_bps: u16,
+    settlement_mode: SettlementMode
+) -> Result<()> {
+    require!(lower < upper, CustomError::InvalidScalarRange);
+    check_price_exponent(&[lower, upper], price_exponent)?;
+
+    ctx.accounts.market.price_exponent = price_exponent;
+    ctx.accounts.market.condition = PriceCondition::Thresholds;
+    ctx.accounts.market.outcome_labels = vec!["SHORT".to_string(), "LONG".to_string()];
+
+    initialize_market(
+        ctx,
+        MarketKind::Scalar { lower, upper },
+        2,
+        oracle_kind,
+        price_feed_id,
+        resolve_from,
+        resolve_to,
+        subsidy_amount,
+        liquidity_b,
+        creator_fee_bps,
+        trading_opens_at,
+        trading_closes_at,
+        confidence_policy,
+        max_price_age,
+        allow_partial_verification,
+        additional_oracles,
+        max_oracle_deviation_bps,
+        settlement_mode,
+    )
+}
+
+/// Checks that the price exponent of a market is within bounds and that its price thresholds
+/// or bounds can be rescaled to `MIN_PRICE_EXPONENT`, the lowest exponent of the oracle prices
+/// they are compared to at resolution
+///
+/// # Arguments
+///
+/// * `prices` - Price thresholds or bounds of the market
+/// * `price_exponent` - Exponent of the prices
+///
+/// # Errors
+///
+/// Returns error if the exponent is out of bounds or a price overflows when rescaled
+fn check_price_exponent(prices: &[i64], price_exponent: i32) -> Result<()> {
+    require!(
+        (MIN_PRICE_EXPONENT..=MAX_PRICE_EXPONENT).contains(&price_exponent),
+        CustomError::InvalidPriceExponent
+    );
+    for price in prices {
+        rescale(*price as i128, price_exponent, MIN_PRICE_EXPONENT)
+            .map_err(|_| error!(CustomError::InvalidPriceExponent))?;
+    }
+    Ok(())
+}
+
+/// Checks that the oracles and the confidence policy of a market support its settlement mode
+///
+/// # Arguments
+///
+/// * `settlement_mode` - Price the market is settled on
+/// * `kind` - Kind of the market
+/// * `oracle_kind` - Oracle provider of the primary price feed
+/// * `additional_oracles` - Price feeds of the other oracle providers
+/// * `confidence_policy` - Policy applied to the confidence interval of the price at resolution
+///
+/// # Errors
+///
+/// Returns error if a moving average market has an oracle without a moving average price, or
+/// a categorical time-weighted average market does not void the market on an ambiguous average
+fn check_settlement_mode(
+    settlement_mode: SettlementMode,
+    kind: MarketKind,
+    oracle_kind: OracleKind,
+    additional_oracles: &[OracleSource],
+    confidence_policy: &ConfidencePolicy,
+) -> Result<()> {
+    // A time-weighted average is final, so an ambiguous average of a categorical market can neither
+    // be rejected nor retried with another price and the market could never be resolved or voided.
+    // Scalar markets pay out on the price itself and are never ambiguous.
+    let is_supported = match settlement_mode {
+        SettlementMode::Spot => true,
+        SettlementMode::Ema => {
+            oracle_adapter(oracle_kind).has_ema_price()
+                && additional_oracles.iter().all(|source| oracle_adapter(source.kind).has_ema_price())
+        }
+        SettlementMode::Twap => {
+            matches!(kind, MarketKind::Scalar { .. }) || confidence_policy.on_ambiguous == AmbiguousPriceAction::Void
+        }
+    };
+    require!(is_supported, CustomError::InvalidSettlementMode);
+    Ok(())
+}
+
+/// Validates the common market parameters, stores them on the market
+/// and transfers the subsidy into the market vault
+#[allow(clippy::too_many_arguments)]
+fn initialize_market(
+    ctx: Context<CreateMarket>,
+    kind: MarketKind,
+    num_outcomes: usize,
+    oracle_kind: OracleKind,
+    price_feed_id: String,
+    resolve_from: u64,
+    resolve_to: u64,
+    subsidy_amount: u64,
+    liquidity_b: u64,
+    creator_fee_bps: u16,
+    trading_opens_at: Option<u64>,
+    trading_closes_at: Option<u64>,
+    confidence_policy: ConfidencePolicy,
+    max_price_age: u64,
+    allow_partial_verification: bool,
+    additional_oracles: Vec<OracleSource>,
+    max_oracle_deviation_bps: u16,
+    settlement_mode: SettlementMode
 ) -> Result<()> {
     require!(resolve_from < resolve_to, CustomError::InvalidResolveWindow);
-    let created_at = Clock::get()?.unix_timestamp.try_into().unwrap();
-    require!(resolve_to >= created_at, CustomError::InvalidResolveTime);
+    let created_at = u64::try_from(Clock::get()?.unix_timestamp).map_err(|_| CustomError::MathOverflow)?;
+    require!(resolve_from >= created_at, CustomError::InvalidResolveTime);
+    let trading_opens_at = trading_opens_at.unwrap_or(created_at);
+    let trading_closes_at = trading_closes_at.unwrap_or(resolve_from);
+    require!(
+        trading_opens_at < trading_closes_at && trading_closes_at <= resolve_from,
+        CustomError::InvalidTradingWindow
+    );
+    require!(
+        (1..=BPS_DENOMINATOR).contains(&(confidence_policy.max_conf_bps as u64)),
+        CustomError::InvalidConfidencePolicy
+    );
+    require!(
+        (1..=ctx.accounts.config.max_price_age).contains(&max_price_age),
+        CustomError::InvalidMaxPriceAge
+    );
+    require!(liquidity_b > 0, CustomError::InvalidLiquidity);
+    require!(creator_fee_bps <= ctx.accounts.config.max_creator_fee_bps, CustomError::InvalidFee);
+    let feed_id = get_feed_id_from_hex(&price_feed_id)?;
+    check_oracle_kind(oracle_kind)?;
+    require!(
+        ctx.accounts.config.allowed_price_feeds.contains(&OracleSource { kind: oracle_kind, feed_id }),
+        CustomError::PriceFeedNotAllowed
+    );
+    require!(additional_oracles.len() < MAX_ORACLE_SOURCES, CustomError::InvalidOracleSources);
+    for (i, source) in additional_oracles.iter().enumerate() {
+        check_oracle_kind(source.kind)?;
+        require!(
+            ctx.accounts.config.allowed_price_feeds.contains(source),
+            CustomError::PriceFeedNotAllowed
+        );
+        let is_distinct = !source.kind.is_same_provider(oracle_kind)
+            && additional_oracles[..i].iter().all(|other| !source.kind.is_same_provider(other.kind));
+        require!(is_distinct, CustomError::InvalidOracleSources);
+    }
+    require!(
+        additional_oracles.is_empty() || (1..=BPS_DENOMINATOR).contains(&(max_oracle_deviation_bps as u64)),
+        CustomError::InvalidOracleSources
+    );
+    check_settlement_mode(settlement_mode, kind, oracle_kind, &additional_oracles, &confidence_policy)?;
+
+    // The maximum loss of an LMSR market maker with n outcomes is b * ln(n)
+    let max_loss = (liquidity_b as u128)
+        .checked_mul(ln(num_outcomes as u128 * SCALE, Rounding::Up)?)
+        .ok_or(CustomError::MathOverflow)?;
+    require!(max_loss <= subsidy_amount as u128 * SCALE, CustomError::InsufficientSubsidy);
+
+    let subsidy_in_tokens = 10u64
+        .checked_pow(ctx.accounts.collateral_mint.decimals as u32)
+        .and_then(|unit| subsidy_amount.checked_mul(unit))
+        .ok_or(CustomError::MathOverflow)?;
 
     // Set market data
     ctx.accounts.market.id = ctx.accounts.metadata.market_counter;
-    ctx.accounts.market.price = price;
+    ctx.accounts.market.creator = ctx.accounts.signer.key();
+    ctx.accounts.market.collateral_mint = ctx.accounts.mint.key();
+    ctx.accounts.market.kind = kind;
+    ctx.accounts.market.oracle_kind = oracle_kind;
     ctx.accounts.market.price_feed_id = price_feed_id;
+    ctx.accounts.market.additional_oracles = additional_oracles;
+    ctx.accounts.market.max_oracle_deviation_bps = max_oracle_deviation_bps;
+    ctx.accounts.market.oracle_readings = Vec::new();
+    ctx.accounts.market.settlement_mode = settlement_mode;
+    ctx.accounts.market.twap = TwapAccumulator::default();
     ctx.accounts.market.resolve_from = resolve_from;
     ctx.accounts.market.resolve_to = resolve_to;
+    ctx.accounts.market.trading_opens_at = trading_opens_at;
+    ctx.accounts.market.trading_closes_at = trading_closes_at;
+    ctx.accounts.market.max_price_age = max_price_age;
+    ctx.accounts.market.allow_partial_verification = allow_partial_verification;
+    ctx.accounts.market.confidence_policy = confidence_policy;
+    ctx.accounts.market.last_ambiguous_publish_time = 0;
     ctx.accounts.market.created_at = created_at;
     ctx.accounts.market.subsidy_amount = subsidy_amount;
-    ctx.accounts.market.current_balance = subsidy_amount * (10u64.pow(ctx.accounts.mint.decimals as u32));
-    ctx.accounts.market.num_outcome_0 = subsidy_amount;
-    ctx.accounts.market.num_outcome_1 = subsidy_amount;
-    ctx.accounts.market.num_outcome_0_held = 0;
-    ctx.accounts.market.num_outcome_1_held = 0;
-    ctx.accounts.market.price_outcome_0 = 0.5f64;
-    ctx.accounts.market.price_outcome_1 = 0.5f64;
+    ctx.accounts.market.liquidity_b = liquidity_b;
+    ctx.accounts.market.current_balance = subsidy_in_tokens;
+    ctx.accounts.market.total_cost_basis = 0;
+    ctx.accounts.market.legacy_cost_basis = 0;
+    ctx.accounts.market.legacy_shares_held = 0;
+    ctx.accounts.market.num_outcomes = num_outcomes as u8;
+    ctx.accounts.market.quantities = vec![subsidy_amount; num_outcomes];
+    ctx.accounts.market.shares_held = vec![0; num_outcomes];
+    ctx.accounts.market.prices = get_prices(&ctx.accounts.market)?;
+    ctx.accounts.market.creator_fee_bps = creator_fee_bps;
+    ctx.accounts.market.creator_fees_accrued = 0;
+    ctx.accounts.market.protocol_fees_accrued = 0;
+    ctx.accounts.market.is_paused = false;
     ctx.accounts.market.is_resolved = false;
+    ctx.accounts.market.is_voided = false;
     ctx.accounts.market.outcome = None;
+    ctx.accounts.market.scalar_payout = 0;
 
     // Increment market counter
-    ctx.accounts.metadata.market_counter += 1;
+    ctx.accounts.metadata.market_counter =
+        ctx.accounts.metadata.market_counter.checked_add(1).ok_or(CustomError::MathOverflow)?;
 
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_accounts = Transfer {
-        from: ctx.accounts.signer_token_account.to_account_info(),
-        to: ctx.accounts.program_token_account.to_account_info(),
-        authority: ctx.accounts.signer.to_account_info(),
-    };
-    transfer(
-        CpiContext::new(
-            cpi_program,
-            cpi_accounts
-        ),
-        subsidy_amount * (10u64.pow(ctx.accounts.mint.decimals as u32)),
+    // Gross up the subsidy by the transfer fee of the mint, if any, so the vault receives it in full
+    transfer_tokens(
+        &ctx.accounts.token_program,
+        &ctx.accounts.signer_token_account,
+        &ctx.accounts.market_vault,
+        &ctx.accounts.signer.to_account_info(),
+        &ctx.accounts.mint,
+        gross_amount(&ctx.accounts.mint, subsidy_in_tokens)?,
+        &[],
     )?;
 
+    // Track what the vault actually received
+    ctx.accounts.market_vault.reload()?;
+    ctx.accounts.market.current_balance = ctx.accounts.market_vault.amount;
+
+    let market = &ctx.accounts.market;
+    emit_cpi!(MarketCreated {
+        market: market.key(),
+        id: market.id,
+        creator: market.creator,
+        collateral_mint: market.collateral_mint,
+        kind: market.kind,
+        price_exponent: market.price_exponent,
+        condition: market.condition,
+        outcome_labels: market.outcome_labels.clone(),
+        num_outcomes: market.num_outcomes,
+        oracle_kind: market.oracle_kind,
+        price_feed_id: market.price_feed_id.clone(),
+        additional_oracles: market.additional_oracles.clone(),
+        max_oracle_deviation_bps: market.max_oracle_deviation_bps,
+        settlement_mode: market.settlement_mode,
+        resolve_from: market.resolve_from,
+        resolve_to: market.resolve_to,
+        trading_opens_at: market.trading_opens_at,
+        trading_closes_at: market.trading_closes_at,
+        subsidy: market.current_balance,
+        liquidity_b: market.liquidity_b,
+        creator_fee_bps: market.creator_fee_bps,
+        max_price_age: market.max_price_age,
+        allow_partial_verification: market.allow_partial_verification,
+        confidence_policy: market.confidence_policy,
+        prices: market.prices.clone(),
+    });
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn price_exponent_must_be_bounded() {
+        assert!(check_price_exponent(&[20_050], -2).is_ok());
+        assert!(check_price_exponent(&[i64::MIN, i64::MAX], MIN_PRICE_EXPONENT).is_ok());
+        assert!(check_price_exponent(&[1, 2], MAX_PRICE_EXPONENT).is_ok());
+        assert!(check_price_exponent(&[], MAX_PRICE_EXPONENT).is_ok());
+
+        for exponent in [MIN_PRICE_EXPONENT - 1, MAX_PRICE_EXPONENT + 1, i32::MIN, i32::MAX] {
+            assert_eq!(
+                check_price_exponent(&[20_050], exponent).unwrap_err(),
+                CustomError::InvalidPriceExponent.into()
+            );
+        }
+        // Rescaled from 10^18 to 10^-18, prices above i128::MAX / 10^36 overflow
+        assert_eq!(
+            check_price_exponent(&[1, i64::MAX], MAX_PRICE_EXPONENT).unwrap_err(),
+            CustomError::InvalidPriceExponent.into()
+        );
+    }
+
+    #[test]
+    fn settlement_mode_must_be_supported_by_oracles_and_policy() {
+        let policy = |on_ambiguous| ConfidencePolicy {
+            conf_multiplier_bps: 20_000,
+            max_conf_bps: 100,
+            on_ambiguous,
+        };
+        let source = |kind| OracleSource { kind, feed_id: [1; 32] };
+        let categorical = MarketKind::Categorical;
+        let reject = policy(AmbiguousPriceAction::Reject);
+        let switchboard = OracleKind::SwitchboardOnDemand;
+
+        assert!(check_settlement_mode(SettlementMode::Spot, categorical, switchboard, &[], &reject).is_ok());
+
+        // Every oracle of a moving average market provides a moving average price
+        let pyth_pull = OracleKind::PythPull;
+        let pyth_push = [source(OracleKind::PythPush)];
+        assert!(check_settlement_mode(SettlementMode::Ema, categorical, pyth_pull, &pyth_push, &reject).is_ok());
+        for (oracle_kind, additional_oracles) in [(switchboard, vec![]), (pyth_pull, vec![source(switchboard)])] {
+            assert_eq!(
+                check_settlement_mode(SettlementMode::Ema, categorical, oracle_kind, &additional_oracles, &reject)
+                    .unwrap_err(),
+                CustomError::InvalidSettlementMode.into()
+            );
+        }
+
+        // A categorical time-weighted average market is voided on an ambiguous average
+        let void = policy(AmbiguousPriceAction::Void);
+        assert!(check_settlement_mode(SettlementMode::Twap, categorical, switchboard, &[], &void).is_ok());
+        for on_ambiguous in [AmbiguousPriceAction::Reject, AmbiguousPriceAction::RetryLater] {
+            assert_eq!(
+                check_settlement_mode(SettlementMode::Twap, categorical, switchboard, &[], &policy(on_ambiguous))
+                    .unwrap_err(),
+                CustomError::InvalidSettlementMode.into()
+            );
+        }
+        // A scalar market is never ambiguous
+        let scalar = MarketKind::Scalar { lower: 150, upper: 250 };
+        assert!(check_settlement_mode(SettlementMode::Twap, scalar, switchboard, &[], &reject).is_ok());
+    }
+}
@@ -0,0 +1,130 @@
+use {
+    anchor_lang::prelude::*,
+    anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use crate::errors::*;
+use crate::state::config::Config;
+use crate::state::market::Market;
+use crate::transfer::transfer_tokens;
+
+/// Accounts required for claiming the creator fees of a market
+#[derive(Accounts)]
+pub struct ClaimCreatorFees<'info> {
+    #[account(has_one = creator @ CustomError::Unauthorized)]
+    pub market: Account<'info, Market>,
+
+    #[account(address = market.collateral_mint @ CustomError::InvalidCollateralMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        seeds = [b"creator_fees", market.key().as_ref()],
+        bump
+    )]
+    pub creator_fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = creator
+    )]
+    pub creator_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub creator: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Accounts required for claiming the protocol fees of a mint
+#[derive(Accounts)]
+pub struct ClaimProtocolFees<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump,
+        has_one = admin @ CustomError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        seeds = [b"protocol_fees", mint.key().as_ref()],
+        bump
+    )]
+    pub protocol_fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = admin
+    )]
+    pub admin_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub admin: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Transfers all fees accrued in a market's creator fee vault to the creator
+///
+/// With a Token-2022 mint that charges a transfer fee, the creator receives the fees minus that fee.
+///
+/// # Arguments
+///
+/// * `ctx` - ClaimCreatorFees context containing required accounts
+///
+/// # Errors
+///
+/// Returns error if the signer is not the market creator or there are no fees to claim
+pub fn claim_creator_fees(ctx: Context<ClaimCreatorFees>) -> Result<()> {
+    let amount = ctx.accounts.creator_fee_vault.amount;
+    require!(amount > 0, CustomError::NoFeesToClaim);
+
+    let market_key = ctx.accounts.market.key();
+    let signer_seeds: &[&[&[u8]]] = &[&[b"creator_fees", market_key.as_ref(), &[ctx.bumps.creator_fee_vault]]];
+    transfer_tokens(
+        &ctx.accounts.token_program,
+        &ctx.accounts.creator_fee_vault,
+        &ctx.accounts.creator_token_account,
+        &ctx.accounts.creator_fee_vault.to_account_info(),
+        &ctx.accounts.mint,
+        amount,
+        signer_seeds,
+    )?;
+
+    Ok(())
+}
+
+/// Transfers all protocol fees accrued for a mint to the admin
+///
+/// With a Token-2022 mint that charges a transfer fee, the admin receives the fees minus that fee.
+///
+/// # Arguments
+///
+/// * `ctx` - ClaimProtocolFees context containing required accounts
+///
+/// # Errors
+///
+/// Returns error if the signer is not the admin or there are no fees to claim
+pub fn claim_protocol_fees(ctx: Context<ClaimProtocolFees>) -> Result<()> {
+    let amount = ctx.accounts.protocol_fee_vault.amount;
+    require!(amount > 0, CustomError::NoFeesToClaim);
+
+    let mint_key = ctx.accounts.mint.key();
+    let signer_seeds: &[&[&[u8]]] = &[&[b"protocol_fees", mint_key.as_ref(), &[ctx.bumps.protocol_fee_vault]]];
+    transfer_tokens(
+        &ctx.accounts.token_program,
+        &ctx.accounts.protocol_fee_vault,
+        &ctx.accounts.admin_token_account,
+        &ctx.accounts.protocol_fee_vault.to_account_info(),
+        &ctx.accounts.mint,
+        amount,
+        signer_seeds,
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,217 @@
+use {
+    anchor_lang::{
+        prelude::*,
+        system_program::{transfer, Transfer},
+        Discriminator,
+    },
+    anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use crate::errors::*;
+use crate::state::collateral::CollateralMint;
+use crate::state::legacy::{deserialize_legacy, LegacyMarket, LegacyOutcomeAccount};
+use crate::state::market::Market;
+use crate::state::outcome::OutcomeAccount;
+use crate::transfer::transfer_tokens;
+
+/// Accounts required for migrating a legacy market to the current market layout
+/// and moving its collateral from the legacy shared token account into its own vault
+#[derive(Accounts)]
+pub struct MigrateMarket<'info> {
+    /// CHECK: a legacy market of this program, checked when deserialized and against the creator
+    #[account(mut, owner = crate::ID)]
+    pub market: UncheckedAccount<'info>,
+
+    /// CHECK: the creator of the market, checked against the seeds of the market address
+    pub creator: UncheckedAccount<'info>,
+
+    #[account(address = legacy_token_account.mint @ CustomError::InvalidCollateralMint)]
+    pub subsidy_mint: InterfaceAccount<'info, Mint>,
+
+    /// The collateral mint must be on the allowlist, so that its protocol fee vault exists
+    #[account(
+        seeds = [b"collateral", subsidy_mint.key().as_ref()],
+        bump
+    )]
+    pub collateral_mint: Account<'info, CollateralMint>,
+
+    #[account(
+        mut,
+        seeds = [b"token"],
+        bump
+    )]
+    pub legacy_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = signer,
+        token::mint = subsidy_mint,
+        token::authority = market_vault,
+        token::token_program = token_program,
+        seeds = [b"vault", market.key().as_ref()],
+        bump
+    )]
+    pub market_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = signer,
+        token::mint = subsidy_mint,
+        token::authority = creator_fee_vault,
+        token::token_program = token_program,
+        seeds = [b"creator_fees", market.key().as_ref()],
+        bump
+    )]
+    pub creator_fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts required for migrating the signer's position in a migrated legacy market
+#[derive(Accounts)]
+pub struct MigratePosition<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+
+    /// CHECK: a legacy position of this program, checked when deserialized
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"outcome", market.key().as_ref(), signer.key().as_ref()],
+        bump
+    )]
+    pub outcome_account: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Migrates a market created before per-market vaults to the current market layout
+///
+/// Reads the legacy layout of the market, with its `f64` predicted price and two outcome
+/// counters, converts it with `LegacyMarket::into_market` and reallocates the account to
+/// the current layout. The market vault and creator fee vault are created, and the market's
+/// balance is transferred out of the shared `[b"token"]` account. Anyone can migrate a market,
+/// paying the rent of the larger account and the vaults. A migrated market no longer has the
+/// legacy layout, so it can only be migrated once.
+///
+/// # Arguments
+///
+/// * `ctx` - MigrateMarket context containing required accounts
+///
+/// # Errors
+///
+/// Returns error if the market does not have the legacy layout, the creator does not match
+/// the market address, the collateral mint is not the mint of the legacy token account or
+/// is not on the allowlist, the predicted price cannot be converted or token transfer fails
+pub fn migrate_market(ctx: Context<MigrateMarket>) -> Result<()> {
+    let legacy = deserialize_legacy::<LegacyMarket>(
+        &ctx.accounts.market.try_borrow_data()?,
+        &Market::DISCRIMINATOR,
+        LegacyMarket::INIT_SPACE,
+    )?;
+
+    // Markets are seeded by their creator, which legacy markets do not store
+    let (market_address, _) = Pubkey::find_program_address(
+        &[b"market", ctx.accounts.creator.key().as_ref(), &legacy.id.to_le_bytes()],
+        ctx.program_id,
+    );
+    require_keys_eq!(market_address, ctx.accounts.market.key(), ErrorCode::ConstraintSeeds);
+
+    let signer_seeds: &[&[&[u8]]] = &[&[b"token", &[ctx.bumps.legacy_token_account]]];
+    transfer_tokens(
+        &ctx.accounts.token_program,
+        &ctx.accounts.legacy_token_account,
+        &ctx.accounts.market_vault,
+        &ctx.accounts.legacy_token_account.to_account_info(),
+        &ctx.accounts.subsidy_mint,
+        legacy.current_balance,
+        signer_seeds,
+    )?;
+
+    let mut market = legacy.into_market(
+        ctx.accounts.creator.key(),
+        ctx.accounts.subsidy_mint.key(),
+        ctx.accounts.subsidy_mint.decimals,
+    )?;
+
+    // Track what the vault received in case the mint charges a transfer fee,
+    // and reserve no more than that for refunds
+    ctx.accounts.market_vault.reload()?;
+    market.current_balance = ctx.accounts.market_vault.amount;
+    market.legacy_cost_basis = market.legacy_cost_basis.min(market.current_balance);
+    market.total_cost_basis = market.legacy_cost_basis;
+
+    write_migrated_account(
+        &ctx.accounts.market,
+        &market,
+        8 + Market::INIT_SPACE,
+        &ctx.accounts.signer,
+        &ctx.accounts.system_program,
+    )
+}
+
+/// Migrates the signer's position in a migrated legacy market to the current position layout
+///
+/// Legacy positions did not track their cost basis, so the migrated position takes its share
+/// of the legacy cost basis of the market with `LegacyOutcomeAccount::into_outcome_account`.
+///
+/// # Arguments
+///
+/// * `ctx` - MigratePosition context containing required accounts
+///
+/// # Errors
+///
+/// Returns error if the position does not have the legacy layout
+/// or holds more shares than the legacy positions of the market
+pub fn migrate_position(ctx: Context<MigratePosition>) -> Result<()> {
+    let legacy = deserialize_legacy::<LegacyOutcomeAccount>(
+        &ctx.accounts.outcome_account.try_borrow_data()?,
+        &OutcomeAccount::DISCRIMINATOR,
+        LegacyOutcomeAccount::INIT_SPACE,
+    )?;
+
+    write_migrated_account(
+        &ctx.accounts.outcome_account,
+        &legacy.into_outcome_account(&mut ctx.accounts.market)?,
+        8 + OutcomeAccount::INIT_SPACE,
+        &ctx.accounts.signer,
+        &ctx.accounts.system_program,
+    )
+}
+
+/// Reallocates a legacy account to the space of its current layout and writes the migrated data
+///
+/// # Arguments
+///
+/// * `account` - Legacy account owned by this program
+/// * `data` - Migrated data of the account
+/// * `space` - Space of the current layout, including the discriminator
+/// * `payer` - Payer of the rent of the additional space
+/// * `system_program` - System program
+fn write_migrated_account<'info, T: AccountSerialize>(
+    account: &UncheckedAccount<'info>,
+    data: &T,
+    space: usize,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space).saturating_sub(account.lamports());
+    if rent > 0 {
+        let cpi_accounts = Transfer {
+            from: payer.to_account_info(),
+            to: account.to_account_info(),
+        };
+        transfer(CpiContext::new(system_program.to_account_info(), cpi_accounts), rent)?;
+    }
+    account.realloc(space, true)?;
+
+    let mut account_data = account.try_borrow_mut_data()?;
+    data.try_serialize(&mut &mut account_data[..])
+}
@@ -3,9 +3,19 @@ pub mod create;
 pub mod buy_sell;
 pub mod resolve;
 pub mod redeem;
+pub mod refund;
+pub mod quote;
+pub mod admin;
+pub mod fees;
+pub mod migrate;
 
 pub use setup::*;
 pub use create::*;
 pub use buy_sell::*;
 pub use resolve::*;
 pub use redeem::*;
+pub use refund::*;
+pub use quote::*;
+pub use admin::*;
+pub use fees::*;
+pub use migrate::*;
@@ -0,0 +1,148 @@
+use {
+    anchor_lang::prelude::*,
+    anchor_spl::token_interface::Mint,
+};
+
+use crate::errors::*;
+use crate::instructions::admin::check_not_paused;
+use crate::instructions::buy_sell::{
+    buy_cost, buy_debit, check_expiry, check_trading_window, get_prices, sell_payout, sell_proceeds, trade_fees,
+};
+use crate::math::{from_token_amount, SCALE};
+use crate::state::config::Config;
+use crate::state::market::Market;
+use crate::transfer::received_amount;
+
+/// Side of a trade on a market
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TradeSide {
+    Buy,
+    Sell,
+}
+
+/// Preview of a hypothetical trade, returned by `quote_trade`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct TradeQuote {
+    /// Cost of a buy or proceeds of a sell in tokens (with decimals), before fees
+    pub amount: u64,
+
+    /// Protocol fee charged on the trade in tokens (with decimals)
+    pub protocol_fee: u64,
+
+    /// Creator fee charged on the trade in tokens (with decimals)
+    pub creator_fee: u64,
+
+    /// Amount debited from the trader for a buy or received for a sell in tokens (with decimals),
+    /// including trading fees and any transfer fee of a Token-2022 mint
+    pub amount_with_fees: u64,
+
+    /// Average fill price per share including trading fees and any transfer fee
+    /// of a Token-2022 mint, scaled by `math::SCALE`
+    pub average_price: u64,
+
+    /// Price of every outcome after the trade, scaled by `math::SCALE`
+    pub prices: Vec<u64>,
+
+    /// Relative difference between the average fill price and the
+    /// pre-trade price of the traded outcome, scaled by `math::SCALE`
+    pub price_impact: u64,
+}
+
+/// Context for quoting a trade without changing any state
+#[derive(Accounts)]
+pub struct QuoteTrade<'info> {
+    pub market: Account<'info, Market>,
+
+    #[account(address = market.collateral_mint @ CustomError::InvalidCollateralMint)]
+    pub subsidy_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+}
+
+/// Quote a hypothetical buy or sell on a market
+///
+/// Runs the same checks and LMSR calculations as `buy_outcome` and `sell_outcome`
+/// on a copy of the market, so simulating this instruction gives an exact
+/// preview of the trade, or fails as the trade would. Only the shares held by
+/// the trader are not checked. The quote is returned via `set_return_data`.
+///
+/// # Arguments
+///
+/// * `ctx` - QuoteTrade context containing required accounts
+/// * `side` - Whether to quote a buy or a sell
+/// * `outcome_idx` - Index of outcome to trade (less than the market's number of outcomes)
+/// * `num_shares` - Number of outcome shares to trade
+/// * `expires_at` - Optional unix timestamp (in seconds) after which the trade is rejected
+///
+/// # Errors
+///
+/// Returns error if market is already resolved, the program or market is paused, trading is closed,
+/// outcome index is invalid, the number of shares is zero, trade has expired or the proceeds
+/// of a sell are too small to pay for the fees
+pub fn quote_trade(
+    ctx: Context<QuoteTrade>,
+    side: TradeSide,
+    outcome_idx: u8,
+    num_shares: u64,
+    expires_at: Option<u64>
+) -> Result<TradeQuote> {
+    require!(!ctx.accounts.market.is_resolved, CustomError::MarketAlreadyResolved);
+    check_not_paused(&ctx.accounts.config, &ctx.accounts.market)?;
+    require!(outcome_idx < ctx.accounts.market.num_outcomes, CustomError::InvalidOutcome);
+    require!(num_shares > 0, CustomError::InvalidAmount);
+    check_trading_window(&ctx.accounts.market, Clock::get()?.unix_timestamp)?;
+    check_expiry(expires_at)?;
+
+    let decimals = ctx.accounts.subsidy_mint.decimals;
+    let mut market: Market = (*ctx.accounts.market).clone();
+    let idx = outcome_idx as usize;
+    let price_before = market.prices[idx];
+
+    let amount = match side {
+        TradeSide::Buy => buy_cost(&market, outcome_idx, num_shares, decimals)?,
+        TradeSide::Sell => sell_proceeds(&market, outcome_idx, num_shares, decimals)?,
+    };
+
+    // Debited from or received by the trader, including trading fees and any transfer fee
+    let mint = &ctx.accounts.subsidy_mint;
+    let fees = trade_fees(amount, ctx.accounts.config.protocol_fee_bps, market.creator_fee_bps)?;
+    let (protocol_fee, creator_fee) = fees;
+    let amount_with_fees = match side {
+        TradeSide::Buy => buy_debit(mint, amount, fees)?,
+        TradeSide::Sell => received_amount(mint, sell_payout(mint, amount, fees)?)?,
+    };
+
+    // Apply the trade to the copy of the market to get post-trade prices
+    let quantity = &mut market.quantities[idx];
+    *quantity = match side {
+        TradeSide::Buy => quantity.checked_add(num_shares),
+        TradeSide::Sell => quantity.checked_sub(num_shares),
+    }
+    .ok_or(CustomError::InsufficientOutcomeAvailable)?;
+    let prices = get_prices(&market)?;
+
+    let average_price = from_token_amount(amount_with_fees, decimals)? / num_shares as u128;
+    let price_impact = if price_before == 0 {
+        0
+    } else {
+        average_price
+            .abs_diff(price_before as u128)
+            .checked_mul(SCALE)
+            .ok_or(CustomError::MathOverflow)?
+            / price_before as u128
+    };
+
+    Ok(TradeQuote {
+        amount,
+        protocol_fee,
+        creator_fee,
+        amount_with_fees,
+        average_price: u64::try_from(average_price).map_err(|_| CustomError::MathOverflow)?,
+        prices,
+        price_impact: u64::try_from(price_impact).map_err(|_| CustomError::MathOverflow)?,
+    })
+}
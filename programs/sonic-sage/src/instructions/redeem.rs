@@ -1,61 +1,92 @@
 use {
     anchor_lang::prelude::*,
-    anchor_spl::{
-        associated_token::AssociatedToken,
-        token::{transfer, Mint, Token, TokenAccount, Transfer},
-    },
+    anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface},
 };
 
 use crate::errors::*;
-use crate::state::market::Market;
+use crate::events::Redeemed;
+use crate::instructions::admin::check_not_paused;
+use crate::math::SCALE;
+use crate::state::config::Config;
+use crate::state::market::{Market, MarketKind, LONG_OUTCOME, SHORT_OUTCOME};
 use crate::state::outcome::OutcomeAccount;
+use crate::transfer::transfer_tokens;
 
 
 /// Accounts required for redeeming outcome tokens after market resolution
+#[event_cpi]
 #[derive(Accounts)]
 pub struct RedeemOutcome<'info> {
     #[account(mut)]
     pub market: Account<'info, Market>,
 
-    #[account(mut)]
-    pub subsidy_mint: Account<'info, Mint>,
+    #[account(address = market.collateral_mint @ CustomError::InvalidCollateralMint)]
+    pub subsidy_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         token::mint = subsidy_mint,
         token::authority = signer
     )]
-    pub signer_token_account: Account<'info, TokenAccount>,
+    pub signer_token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
-        seeds = [b"token"],
+        token::mint = subsidy_mint,
+        seeds = [b"vault", market.key().as_ref()],
         bump
     )]
-    pub program_token_account: Account<'info, TokenAccount>,
+    pub market_vault: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
-        init_if_needed,
-        payer = signer,
-        space = 8 + OutcomeAccount::INIT_SPACE,
+        mut,
+        close = signer,
         seeds = [b"outcome", market.key().as_ref(), signer.key().as_ref()],
         bump
     )]
     pub outcome_account: Account<'info, OutcomeAccount>,
 
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
     #[account(mut)]
     pub signer: Signer<'info>,
 
-    pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Accounts required for closing a position without a payout
+#[derive(Accounts)]
+pub struct ClosePosition<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        mut,
+        close = signer,
+        seeds = [b"outcome", market.key().as_ref(), signer.key().as_ref()],
+        bump
+    )]
+    pub outcome_account: Account<'info, OutcomeAccount>,
+
+    #[account(mut)]
+    pub signer: Signer<'info>,
 }
 
 /// Redeems winning outcome tokens after market resolution
 ///
 /// Calculates the user's share of the total market pool based on their
 /// proportion of winning outcome shares and transfers the corresponding
-/// amount of tokens to their account
+/// amount of tokens to their account. For a scalar market both LONG and
+/// SHORT shares are redeemed, weighted by their payout.
+///
+/// The payout is paid out of the market pool, so with a Token-2022 mint that
+/// charges a transfer fee the user receives the payout minus that fee.
+///
+/// The position is settled and the outcome account is closed, returning its rent to the user.
 ///
 /// # Arguments
 ///
@@ -63,31 +94,151 @@ pub struct RedeemOutcome<'info> {
 ///
 /// # Errors
 ///
-/// Returns error if market is not yet resolved or token transfer fails
+/// Returns error if market is not yet resolved, market is voided, the program or market
+/// is paused or token transfer fails
 pub fn redeem_outcome(ctx: Context<RedeemOutcome>) -> Result<()> {
     require!(ctx.accounts.market.is_resolved, CustomError::MarketNotResolvedYet);
+    require!(!ctx.accounts.market.is_voided, CustomError::MarketVoided);
+    check_not_paused(&ctx.accounts.config, &ctx.accounts.market)?;
 
-    // Calculate the winning outcome amount
-    let winning_outcome = ctx.accounts.market.outcome.unwrap();
-    let (num_outcomes, total_winning_outcome) = if winning_outcome == 0 { 
-        (ctx.accounts.outcome_account.amount_0, ctx.accounts.market.num_outcome_0_held)
-    } else { 
-        (ctx.accounts.outcome_account.amount_1, ctx.accounts.market.num_outcome_1_held)
-    };
-    let total_token_balance = ctx.accounts.market.current_balance;
-    let win_amount = (num_outcomes * total_token_balance) / total_winning_outcome;
+    let win_amount = redeem_amount(&ctx.accounts.market, &ctx.accounts.outcome_account)?;
 
     // Transfer the winning amount to the signer
-    let signer_seeds: &[&[&[u8]]] = &[&[b"token", &[ctx.bumps.program_token_account]]];
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_accounts = Transfer {
-        from: ctx.accounts.program_token_account.to_account_info(),
-        to: ctx.accounts.signer_token_account.to_account_info(),
-        authority: ctx.accounts.program_token_account.to_account_info(),
+    let market_key = ctx.accounts.market.key();
+    let signer_seeds: &[&[&[u8]]] = &[&[b"vault", market_key.as_ref(), &[ctx.bumps.market_vault]]];
+    transfer_tokens(
+        &ctx.accounts.token_program,
+        &ctx.accounts.market_vault,
+        &ctx.accounts.signer_token_account,
+        &ctx.accounts.market_vault.to_account_info(),
+        &ctx.accounts.subsidy_mint,
+        win_amount,
+        signer_seeds,
+    )?;
+
+    ctx.accounts.market.current_balance =
+        ctx.accounts.market.current_balance.checked_sub(win_amount).ok_or(CustomError::MathOverflow)?;
+    settle_position(&mut ctx.accounts.market, &mut ctx.accounts.outcome_account)?;
+
+    emit_cpi!(Redeemed {
+        market: market_key,
+        user: ctx.accounts.signer.key(),
+        amount: win_amount,
+    });
+
+    Ok(())
+}
+
+/// Closes a position that has nothing left to redeem or refund, returning its rent to the user
+///
+/// Empty positions can be closed at any time, losing positions once the market is resolved.
+///
+/// # Arguments
+///
+/// * `ctx` - ClosePosition context containing required accounts
+///
+/// # Errors
+///
+/// Returns error if the position still has a payout or a refund to claim
+pub fn close_position(ctx: Context<ClosePosition>) -> Result<()> {
+    let market = &ctx.accounts.market;
+    let outcome_account = &ctx.accounts.outcome_account;
+    let is_empty = outcome_account.amounts.iter().all(|amount| *amount == 0) && outcome_account.cost_basis == 0;
+    let has_claim = if market.is_voided {
+        outcome_account.cost_basis > 0
+    } else if market.is_resolved {
+        redeem_amount(market, outcome_account)? > 0
+    } else {
+        !is_empty
     };
-    transfer(CpiContext::new(cpi_program, cpi_accounts).with_signer(signer_seeds), win_amount)?;
+    require!(!has_claim, CustomError::PositionNotClosable);
 
-    ctx.accounts.market.current_balance -= win_amount;
+    settle_position(&mut ctx.accounts.market, &mut ctx.accounts.outcome_account)?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Calculates the redeem amount of a user's position in a resolved market
+///
+/// # Errors
+///
+/// Returns error if the market has no winning outcome or no winning shares are held
+///
+/// # Returns
+///
+/// The amount of tokens (with decimals) the user receives
+pub fn redeem_amount(market: &Market, outcome_account: &OutcomeAccount) -> Result<u64> {
+    match market.kind {
+        MarketKind::Categorical => {
+            // Calculate the winning outcome amount
+            let winning_outcome = market.outcome.ok_or(CustomError::MarketNotResolvedYet)?;
+            let num_outcomes = outcome_account.amounts.get(winning_outcome as usize).copied().unwrap_or_default();
+            if num_outcomes == 0 {
+                return Ok(0);
+            }
+            let total_winning_outcome = market
+                .shares_held
+                .get(winning_outcome as usize)
+                .copied()
+                .filter(|shares| *shares > 0)
+                .ok_or(CustomError::NoWinningShares)?;
+            let amount = (num_outcomes as u128)
+                .checked_mul(market.current_balance as u128)
+                .ok_or(CustomError::MathOverflow)?
+                / total_winning_outcome as u128;
+            Ok(u64::try_from(amount).map_err(|_| CustomError::MathOverflow)?)
+        }
+        MarketKind::Scalar { .. } => scalar_redeem_amount(market, outcome_account),
+    }
+}
+
+/// Settles a position by removing its shares and cost basis from the market totals
+/// and clearing the outcome account, which is then closed by the caller
+///
+/// # Errors
+///
+/// Returns error if the position holds more shares or cost basis than the market totals
+pub fn settle_position(market: &mut Market, outcome_account: &mut OutcomeAccount) -> Result<()> {
+    for (held, amount) in market.shares_held.iter_mut().zip(outcome_account.amounts.iter()) {
+        *held = held.checked_sub(*amount).ok_or(CustomError::MathOverflow)?;
+    }
+    market.total_cost_basis =
+        market.total_cost_basis.checked_sub(outcome_account.cost_basis).ok_or(CustomError::MathOverflow)?;
+
+    outcome_account.amounts.clear();
+    outcome_account.cost_basis = 0;
+
+    Ok(())
+}
+
+/// Calculates the redeem amount of a user's LONG and SHORT shares of a resolved scalar market
+///
+/// The user's claim is their shares weighted by the payout of each side, and the
+/// market pool is split pro-rata between all claims.
+///
+/// # Returns
+///
+/// The amount of tokens (with decimals) the user receives
+fn scalar_redeem_amount(market: &Market, outcome_account: &OutcomeAccount) -> Result<u64> {
+    let long_payout = market.scalar_payout as u128;
+    let short_payout = SCALE - long_payout;
+    let weighted = |long: u64, short: u64| {
+        (long as u128 * long_payout).checked_add(short as u128 * short_payout).ok_or(CustomError::MathOverflow)
+    };
+
+    let shares = |outcome: u8| outcome_account.amounts.get(outcome as usize).copied().unwrap_or_default();
+    let claim = weighted(shares(LONG_OUTCOME), shares(SHORT_OUTCOME))?;
+    let total_claims = weighted(
+        market.shares_held[LONG_OUTCOME as usize],
+        market.shares_held[SHORT_OUTCOME as usize],
+    )?;
+    if total_claims == 0 {
+        return Ok(0);
+    }
+
+    let amount = claim
+        .checked_mul(market.current_balance as u128)
+        .ok_or(CustomError::MathOverflow)?
+        / total_claims;
+    Ok(u64::try_from(amount).map_err(|_| CustomError::MathOverflow)?)
+}
@@ -0,0 +1,200 @@
+use {
+    anchor_lang::prelude::*,
+    anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use crate::errors::*;
+use crate::instructions::admin::check_not_paused;
+use crate::instructions::redeem::settle_position;
+use crate::state::config::Config;
+use crate::state::market::Market;
+use crate::state::outcome::OutcomeAccount;
+use crate::transfer::transfer_tokens;
+
+/// Accounts required for refunding the cost basis of a position in a voided market
+#[derive(Accounts)]
+pub struct RefundOutcome<'info> {
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+
+    #[account(address = market.collateral_mint @ CustomError::InvalidCollateralMint)]
+    pub subsidy_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = subsidy_mint,
+        token::authority = signer
+    )]
+    pub signer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = subsidy_mint,
+        seeds = [b"vault", market.key().as_ref()],
+        bump
+    )]
+    pub market_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        close = signer,
+        seeds = [b"outcome", market.key().as_ref(), signer.key().as_ref()],
+        bump
+    )]
+    pub outcome_account: Account<'info, OutcomeAccount>,
+
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Accounts required for the creator to reclaim the remaining subsidy of a voided market
+#[derive(Accounts)]
+pub struct ReclaimSubsidy<'info> {
+    #[account(
+        mut,
+        has_one = creator @ CustomError::Unauthorized
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(address = market.collateral_mint @ CustomError::InvalidCollateralMint)]
+    pub subsidy_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = subsidy_mint,
+        token::authority = creator
+    )]
+    pub creator_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = subsidy_mint,
+        seeds = [b"vault", market.key().as_ref()],
+        bump
+    )]
+    pub market_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+
+    pub creator: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Refunds the cost basis of the signer's position in a voided market
+///
+/// The full cost basis is refunded if the market balance covers the cost basis
+/// of all positions, otherwise the balance is shared pro-rata between them.
+/// With a Token-2022 mint that charges a transfer fee, the user receives the refund minus that fee.
+/// The position is settled and the outcome account is closed, returning its rent to the user.
+///
+/// # Arguments
+///
+/// * `ctx` - RefundOutcome context containing required accounts
+///
+/// # Errors
+///
+/// Returns error if the market is not voided, the program or market is paused or there is nothing to refund
+pub fn refund_outcome(ctx: Context<RefundOutcome>) -> Result<()> {
+    require!(ctx.accounts.market.is_voided, CustomError::MarketNotVoided);
+    check_not_paused(&ctx.accounts.config, &ctx.accounts.market)?;
+    let cost_basis = ctx.accounts.outcome_account.cost_basis;
+    require!(cost_basis > 0, CustomError::NothingToRefund);
+
+    let refund_amount = refund_amount(&ctx.accounts.market, cost_basis);
+
+    let market_key = ctx.accounts.market.key();
+    let signer_seeds: &[&[&[u8]]] = &[&[b"vault", market_key.as_ref(), &[ctx.bumps.market_vault]]];
+    transfer_tokens(
+        &ctx.accounts.token_program,
+        &ctx.accounts.market_vault,
+        &ctx.accounts.signer_token_account,
+        &ctx.accounts.market_vault.to_account_info(),
+        &ctx.accounts.subsidy_mint,
+        refund_amount,
+        signer_seeds,
+    )?;
+
+    ctx.accounts.market.current_balance =
+        ctx.accounts.market.current_balance.checked_sub(refund_amount).ok_or(CustomError::MathOverflow)?;
+    settle_position(&mut ctx.accounts.market, &mut ctx.accounts.outcome_account)?;
+
+    Ok(())
+}
+
+/// Transfers the subsidy left in a voided market, after reserving the
+/// cost basis of all positions, back to the market creator
+///
+/// With a Token-2022 mint that charges a transfer fee, the creator receives the subsidy minus that fee.
+///
+/// # Arguments
+///
+/// * `ctx` - ReclaimSubsidy context containing required accounts
+///
+/// # Errors
+///
+/// Returns error if the signer is not the market creator, the market is not voided,
+/// the program or market is paused or there is no subsidy left to reclaim
+pub fn reclaim_subsidy(ctx: Context<ReclaimSubsidy>) -> Result<()> {
+    require!(ctx.accounts.market.is_voided, CustomError::MarketNotVoided);
+    check_not_paused(&ctx.accounts.config, &ctx.accounts.market)?;
+    let amount = reclaimable_subsidy(&ctx.accounts.market);
+    require!(amount > 0, CustomError::NoSubsidyToReclaim);
+
+    let market_key = ctx.accounts.market.key();
+    let signer_seeds: &[&[&[u8]]] = &[&[b"vault", market_key.as_ref(), &[ctx.bumps.market_vault]]];
+    transfer_tokens(
+        &ctx.accounts.token_program,
+        &ctx.accounts.market_vault,
+        &ctx.accounts.creator_token_account,
+        &ctx.accounts.market_vault.to_account_info(),
+        &ctx.accounts.subsidy_mint,
+        amount,
+        signer_seeds,
+    )?;
+
+    ctx.accounts.market.current_balance =
+        ctx.accounts.market.current_balance.checked_sub(amount).ok_or(CustomError::MathOverflow)?;
+
+    Ok(())
+}
+
+/// Calculates the refund of a position in a voided market
+///
+/// # Arguments
+///
+/// * `market` - Voided market of the position
+/// * `cost_basis` - Cost basis of the position
+///
+/// # Returns
+///
+/// The cost basis of the position, or its pro-rata share of the market balance
+/// if that does not cover the cost basis of all positions
+pub fn refund_amount(market: &Market, cost_basis: u64) -> u64 {
+    if market.current_balance >= market.total_cost_basis {
+        cost_basis
+    } else {
+        (cost_basis as u128 * market.current_balance as u128 / market.total_cost_basis as u128) as u64
+    }
+}
+
+/// Calculates the subsidy the creator can reclaim from a voided market
+///
+/// # Returns
+///
+/// The market balance left after reserving the cost basis of all positions
+pub fn reclaimable_subsidy(market: &Market) -> u64 {
+    market.current_balance.saturating_sub(market.total_cost_basis)
+}
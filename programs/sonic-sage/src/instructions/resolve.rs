@@ -1,11 +1,19 @@
-use std::ops::Div;
-
 use anchor_lang::prelude::*;
-use pyth_solana_receiver_sdk::price_update::{PriceUpdateV2, get_feed_id_from_hex};
+use pyth_solana_receiver_sdk::price_update::get_feed_id_from_hex;
 
-use crate::state::market::Market;
+use crate::errors::*;
+use crate::events::{MarketResolved, MarketVoided, ObservationRecorded};
+use crate::instructions::admin::check_not_paused;
+use crate::math::{rescale, SCALE};
+use crate::oracle::{median_price, oracle_adapter, OraclePrice};
+use crate::state::config::{Config, BPS_DENOMINATOR};
+use crate::state::market::{
+    AmbiguousPriceAction, Market, MarketKind, OracleKind, OracleReading, PriceCondition, SettlementMode,
+    MIN_TWAP_OBSERVATIONS,
+};
 
-/// Context accounts for resolving a prediction market using Pyth price feeds
+/// Context accounts for resolving a prediction market using the price feed of its oracle provider
+#[event_cpi]
 #[derive(Accounts)]
 pub struct ResolveMarket<'info> {
     #[account(mut)]
@@ -14,42 +22,652 @@ pub struct ResolveMarket<'info> {
     #[account(mut)]
     pub market: Account<'info, Market>,
 
-    pub price_update: Account<'info, PriceUpdateV2>,
+    /// CHECK: validated by the oracle adapter of the market's oracle kind
+    pub oracle: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+}
+
+/// Context accounts for recording an oracle price observation of a market settled on a time-weighted average price
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RecordObservation<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+
+    /// CHECK: validated by the oracle adapter of the market's oracle kind
+    pub oracle: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
 }
 
+/// Context accounts for voiding a market that was not resolved in time
+#[event_cpi]
+#[derive(Accounts)]
+pub struct VoidMarket<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(mut)]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+}
 
-/// Resolves a market by comparing the target price to the actual price from Pyth oracle
+/// Resolves a market by comparing the price thresholds to the actual price from its oracles
+///
+/// The price is read from the oracle account by the adapter of the market's oracle kind,
+/// Pyth pull or push, Switchboard On-Demand or the mock oracle. A market with additional
+/// oracles takes their accounts as remaining accounts, in order, and resolves on the median
+/// of all the prices, which must not spread by more than `max_oracle_deviation_bps` of the
+/// median. The prices read are recorded on the market.
+///
+/// The market is settled on the spot price, the Pyth moving average price, or the
+/// time-weighted average of the prices observed with `record_observation`, according to
+/// its settlement mode. A time-weighted average is settled once the resolve window has
+/// closed and at least `MIN_TWAP_OBSERVATIONS` prices were observed.
 ///
-/// Uses Pyth price feed to determine the winning outcome:
+/// The actual price determines the winning outcome according to the market's condition.
+/// With `PriceCondition::Thresholds`, outcome `i` wins if exactly `i` thresholds are below
+/// the actual price. For a binary market:
 /// - If target price >= actual price: Outcome 0 wins
 /// - If target price < actual price: Outcome 1 wins
 ///
+/// With a comparison to the threshold or a `[low, high)` bracket, outcome 0 wins if the
+/// condition holds and outcome 1 otherwise.
+///
+/// The market can only be resolved once `resolve_from` has passed, using fully verified
+/// prices, or partially verified ones if the market allows it, published within the
+/// `[resolve_from, resolve_to]` window and no older than the market's `max_price_age`.
+///
+/// The confidence interval of every price must not exceed `max_conf_bps` of the price.
+/// For a categorical market, a price within `k * conf` of a threshold, taking the widest
+/// confidence interval of the oracles, is ambiguous and handled according to the market's
+/// confidence policy: the price update is rejected, the market stays unresolved until a
+/// later price is submitted, or the market is voided.
+///
+/// The thresholds and scalar bounds are compared to the price in integer math, after
+/// rescaling both sides to the lowest of the market and price feed exponents.
+///
+/// For a scalar market the actual price is clamped to the `[lower, upper]` range and
+/// the LONG payout `(clamp(price) - lower) / (upper - lower)` is stored on the market.
+///
 /// # Arguments
 ///
 /// * `ctx` - ResolveMarket context containing required accounts
 ///
 /// # Errors
 ///
-/// Returns error if price feed ID is invalid or price data is too old
-pub fn resolve_market(ctx: Context<ResolveMarket>) -> Result<()> {
-    let price_update = &mut ctx.accounts.price_update;
-    let maximum_age: u64 = 60;
-    let feed_id: [u8; 32] = get_feed_id_from_hex(&ctx.accounts.market.price_feed_id)?;
-    let price = price_update.get_price_no_older_than(&Clock::get()?, maximum_age, &feed_id)?;
-    msg!("The price is ({} ± {}) * 10^{}", price.price, price.conf, price.exponent);
+/// Returns error if the market is already resolved, the program or market is paused,
+/// the resolve window has not opened yet, price feed ID is invalid, the oracle accounts do
+/// not match the oracles of the market, an oracle account is not a valid account of its
+/// oracle provider or is for another price feed, a price is not fully verified and the
+/// market does not allow partial verification, is older than the maximum price age, was not
+/// published within the resolve window or after the last ambiguous price, or its confidence
+/// interval is too wide, the prices disagree, a time-weighted average market has not closed
+/// its resolve window or has too few observations, or the price is ambiguous and the
+/// confidence policy rejects it
+pub fn resolve_market<'info>(ctx: Context<'_, '_, 'info, 'info, ResolveMarket<'info>>) -> Result<()> {
+    require!(!ctx.accounts.market.is_resolved, CustomError::MarketAlreadyResolved);
+    check_not_paused(&ctx.accounts.config, &ctx.accounts.market)?;
+    let now = Clock::get()?.unix_timestamp;
+    require!(now >= ctx.accounts.market.resolve_from as i64, CustomError::ResolveWindowNotOpen);
 
-    let pos_exp = (-1 * price.exponent) as u32;
-    let actual_price = (price.price as f64).div(10u64.pow(pos_exp as u32) as f64);
-    let target_price = ctx.accounts.market.price;
-    msg!("Actual price: {}", actual_price);
-    msg!("Target price: {}", target_price);
+    let market = &ctx.accounts.market;
+    let policy = market.confidence_policy;
+    let market_exponent = market.price_exponent;
+    let price = match market.settlement_mode {
+        SettlementMode::Spot | SettlementMode::Ema => {
+            let (price, readings) = read_median_price(market, &ctx.accounts.oracle, ctx.remaining_accounts, now)?;
+            ctx.accounts.market.oracle_readings = readings;
+            price
+        }
+        SettlementMode::Twap => {
+            require!(now > market.resolve_to as i64, CustomError::ResolveWindowNotClosed);
+            require!(market.twap.observations >= MIN_TWAP_OBSERVATIONS, CustomError::InsufficientObservations);
+            OraclePrice {
+                price: market.twap.average(market.resolve_to as i64)?,
+                conf: market.twap.max_conf,
+                exponent: market.twap.exponent,
+                publish_time: market.resolve_to as i64,
+            }
+        }
+    };
 
-    if target_price >= actual_price {
-        ctx.accounts.market.outcome = Some(0);
-    } else {
-        ctx.accounts.market.outcome = Some(1);
+    // Compare prices exactly at the lower of the market and oracle exponents
+    let exponent = price.exponent;
+    let actual_price = price.price;
+    msg!("Actual price: ({} ± {}) * 10^{}", actual_price, price.conf, exponent);
+
+    match ctx.accounts.market.kind {
+        MarketKind::Categorical => {
+            let price_thresholds = ctx
+                .accounts
+                .market
+                .price_thresholds
+                .iter()
+                .map(|threshold| rescale(*threshold as i128, market_exponent, exponent))
+                .collect::<Result<Vec<i128>>>()?;
+            msg!("Price thresholds: {:?} * 10^{}", price_thresholds, exponent);
+
+            let is_ambiguous = is_ambiguous(&price_thresholds, actual_price, price.conf, policy.conf_multiplier_bps)?;
+            let winning_outcome = winning_outcome(ctx.accounts.market.condition, &price_thresholds, actual_price)?;
+
+            if !is_ambiguous {
+                ctx.accounts.market.outcome = Some(winning_outcome as u8);
+            } else {
+                match policy.on_ambiguous {
+                    AmbiguousPriceAction::Reject => return err!(CustomError::PriceWithinConfidence),
+                    AmbiguousPriceAction::RetryLater => {
+                        msg!("Ambiguous price, retry with a price published later");
+                        ctx.accounts.market.last_ambiguous_publish_time = price.publish_time as u64;
+                        return Ok(());
+                    }
+                    AmbiguousPriceAction::Void => {
+                        msg!("Ambiguous price, voiding the market");
+                        ctx.accounts.market.is_voided = true;
+                    }
+                }
+            }
+        }
+        MarketKind::Scalar { lower, upper } => {
+            let lower = rescale(lower as i128, market_exponent, exponent)?;
+            let upper = rescale(upper as i128, market_exponent, exponent)?;
+            msg!("Scalar range: [{}, {}] * 10^{}", lower, upper, exponent);
+
+            // LONG payout (clamp(price) - lower) / (upper - lower), scaled by SCALE
+            let range = upper.checked_sub(lower).ok_or(CustomError::MathOverflow)?;
+            let long_payout = actual_price
+                .clamp(lower, upper)
+                .checked_sub(lower)
+                .and_then(|distance| (distance as u128).checked_mul(SCALE))
+                .ok_or(CustomError::MathOverflow)?
+                / range as u128;
+            ctx.accounts.market.scalar_payout = long_payout as u64;
+            msg!("LONG payout: {}", ctx.accounts.market.scalar_payout);
+        }
     }
     ctx.accounts.market.is_resolved = true;
 
+    emit_cpi!(MarketResolved {
+        market: ctx.accounts.market.key(),
+        outcome: ctx.accounts.market.outcome,
+        is_voided: ctx.accounts.market.is_voided,
+        scalar_payout: ctx.accounts.market.scalar_payout,
+        price: price.price,
+        conf: price.conf,
+        exponent: price.exponent,
+        publish_time: price.publish_time,
+    });
+
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Records an observation of the oracle prices of a market settled on a time-weighted average price
+///
+/// Anyone can record observations during the resolve window. The observed price is the median
+/// of the market's oracles, read and checked as in `resolve_market`, and must be published after
+/// the last observation. Once the window has closed, `resolve_market` settles the market on the
+/// time-weighted average of the observations.
+///
+/// # Arguments
+///
+/// * `ctx` - RecordObservation context containing required accounts
+///
+/// # Errors
+///
+/// Returns error if the market is already resolved or not settled on a time-weighted average price,
+/// the program or market is paused, the resolve window has not opened yet, the oracle prices cannot
+/// resolve the market or the price is not published after the last observation
+pub fn record_observation<'info>(ctx: Context<'_, '_, 'info, 'info, RecordObservation<'info>>) -> Result<()> {
+    require!(!ctx.accounts.market.is_resolved, CustomError::MarketAlreadyResolved);
+    check_not_paused(&ctx.accounts.config, &ctx.accounts.market)?;
+    require!(ctx.accounts.market.settlement_mode == SettlementMode::Twap, CustomError::NotTwapMarket);
+    let now = Clock::get()?.unix_timestamp;
+    require!(now >= ctx.accounts.market.resolve_from as i64, CustomError::ResolveWindowNotOpen);
+
+    let (price, readings) =
+        read_median_price(&ctx.accounts.market, &ctx.accounts.oracle, ctx.remaining_accounts, now)?;
+    let market = &mut ctx.accounts.market;
+    market.twap.record(price.price, price.conf, price.exponent, price.publish_time)?;
+    market.oracle_readings = readings;
+
+    emit_cpi!(ObservationRecorded {
+        market: market.key(),
+        price: price.price,
+        conf: price.conf,
+        exponent: price.exponent,
+        publish_time: price.publish_time,
+        observations: market.twap.observations,
+    });
+
+    Ok(())
+}
+
+/// Reads the prices of all oracle sources of a market and combines them into their median
+///
+/// The primary oracle is the `oracle` account, the additional oracles follow in the remaining
+/// accounts in order.
+///
+/// # Arguments
+///
+/// * `market` - Market to resolve
+/// * `oracle` - Account of the primary oracle
+/// * `remaining_accounts` - Accounts of the additional oracles
+/// * `now` - Current unix timestamp in seconds
+///
+/// # Errors
+///
+/// Returns error if the oracle accounts do not match the oracles of the market, a price
+/// cannot resolve the market or the prices disagree
+///
+/// # Returns
+///
+/// The median price and the prices of every oracle source
+fn read_median_price<'info>(
+    market: &Market,
+    oracle: &AccountInfo<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    now: i64,
+) -> Result<(OraclePrice, Vec<OracleReading>)> {
+    require!(
+        remaining_accounts.len() == market.additional_oracles.len(),
+        CustomError::OracleAccountsMismatch
+    );
+    let mut sources = vec![(market.oracle_kind, get_feed_id_from_hex(&market.price_feed_id)?, oracle)];
+    for (source, account) in market.additional_oracles.iter().zip(remaining_accounts) {
+        sources.push((source.kind, source.feed_id, account));
+    }
+    let prices = sources
+        .iter()
+        .map(|(kind, feed_id, account)| read_oracle_price(market, *kind, account, feed_id, now))
+        .collect::<Result<Vec<OraclePrice>>>()?;
+
+    let price = median_price(&prices, market.price_exponent, market.max_oracle_deviation_bps)?;
+    let readings = prices
+        .iter()
+        .zip(&sources)
+        .map(|(reading, (kind, _, _))| OracleReading {
+            kind: *kind,
+            price: reading.price,
+            conf: reading.conf,
+            exponent: reading.exponent,
+            publish_time: reading.publish_time,
+        })
+        .collect();
+    Ok((price, readings))
+}
+
+/// Reads the price of an oracle source of a market and checks that it can resolve the market
+///
+/// # Arguments
+///
+/// * `market` - Market to resolve
+/// * `kind` - Oracle provider of the price feed
+/// * `oracle` - Account of the oracle provider holding the price
+/// * `feed_id` - ID of the price feed
+/// * `now` - Current unix timestamp in seconds
+///
+/// # Errors
+///
+/// Returns error if the oracle adapter rejects the account, the price is older than the
+/// maximum price age, was not published within the resolve window or after the last
+/// ambiguous price, or its confidence interval is too wide
+fn read_oracle_price(
+    market: &Market,
+    kind: OracleKind,
+    oracle: &AccountInfo,
+    feed_id: &[u8; 32],
+    now: i64,
+) -> Result<OraclePrice> {
+    let adapter = oracle_adapter(kind);
+    let price = match market.settlement_mode {
+        SettlementMode::Ema => adapter.read_ema_price(oracle, feed_id, market.allow_partial_verification)?,
+        SettlementMode::Spot | SettlementMode::Twap => {
+            adapter.read_price(oracle, feed_id, market.allow_partial_verification)?
+        }
+    };
+    msg!(
+        "The {:?} price is ({} ± {}) * 10^{} published at {}",
+        kind,
+        price.price,
+        price.conf,
+        price.exponent,
+        price.publish_time
+    );
+    check_publish_time(market, price.publish_time, now)?;
+    check_confidence(&price, market.confidence_policy.max_conf_bps)?;
+
+    Ok(price)
+}
+
+/// Checks that the confidence interval of an oracle price is narrow enough relative to the price
+///
+/// # Arguments
+///
+/// * `price` - Oracle price with its confidence interval
+/// * `max_conf_bps` - Maximum ratio of the confidence interval to the price, in basis points
+///
+/// # Errors
+///
+/// Returns error if the confidence interval exceeds `max_conf_bps` of the price
+fn check_confidence(price: &OraclePrice, max_conf_bps: u16) -> Result<()> {
+    let conf_ratio = price.conf.checked_mul(BPS_DENOMINATOR as u128).ok_or(CustomError::MathOverflow)?;
+    let max_conf_ratio = price
+        .price
+        .unsigned_abs()
+        .checked_mul(max_conf_bps as u128)
+        .ok_or(CustomError::MathOverflow)?;
+    require!(conf_ratio <= max_conf_ratio, CustomError::ConfidenceTooWide);
+    Ok(())
+}
+
+/// Checks whether a price is too close to a threshold to tell the outcomes apart
+///
+/// A price is ambiguous if `|price - threshold| < k * conf` for any of the thresholds,
+/// where `k` is the confidence multiplier of the market's confidence policy.
+///
+/// # Arguments
+///
+/// * `price_thresholds` - Thresholds of the market, at the exponent of the price
+/// * `price` - Actual price
+/// * `conf` - Confidence interval of the price, at the same exponent
+/// * `conf_multiplier_bps` - Multiple `k` of the confidence interval, in basis points
+fn is_ambiguous(price_thresholds: &[i128], price: i128, conf: u128, conf_multiplier_bps: u32) -> Result<bool> {
+    let ambiguity = conf.checked_mul(conf_multiplier_bps as u128).ok_or(CustomError::MathOverflow)?;
+    for threshold in price_thresholds {
+        let distance = price
+            .abs_diff(*threshold)
+            .checked_mul(BPS_DENOMINATOR as u128)
+            .ok_or(CustomError::MathOverflow)?;
+        if distance < ambiguity {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Evaluates the price condition of a categorical market on the actual price
+///
+/// # Arguments
+///
+/// * `condition` - Price condition of the market
+/// * `price_thresholds` - Thresholds of the market, at the exponent of the price
+/// * `price` - Actual price
+///
+/// # Errors
+///
+/// Returns error if the number of thresholds does not match the condition
+///
+/// # Returns
+///
+/// The index of the winning outcome, outcome 0 if a binary condition holds and outcome 1 otherwise
+fn winning_outcome(condition: PriceCondition, price_thresholds: &[i128], price: i128) -> Result<usize> {
+    if condition == PriceCondition::Thresholds {
+        return Ok(price_thresholds.iter().filter(|threshold| **threshold < price).count());
+    }
+
+    let holds = match (condition, price_thresholds) {
+        (PriceCondition::GreaterThan, [threshold]) => price > *threshold,
+        (PriceCondition::GreaterOrEqual, [threshold]) => price >= *threshold,
+        (PriceCondition::LessThan, [threshold]) => price < *threshold,
+        (PriceCondition::LessOrEqual, [threshold]) => price <= *threshold,
+        (PriceCondition::Inside, [low, high]) => (*low..*high).contains(&price),
+        _ => return err!(CustomError::InvalidPriceCondition),
+    };
+    Ok(if holds { 0 } else { 1 })
+}
+
+/// Checks that an oracle price is recent enough and published at a time it can resolve the market
+///
+/// # Arguments
+///
+/// * `market` - Market to resolve
+/// * `publish_time` - Publish time of the price (unix timestamp in seconds)
+/// * `now` - Current unix timestamp in seconds
+///
+/// # Errors
+///
+/// Returns error if the price is older than the maximum price age of the market, was not
+/// published within the resolve window or after the last ambiguous price
+fn check_publish_time(market: &Market, publish_time: i64, now: i64) -> Result<()> {
+    require!(publish_time.saturating_add(market.max_price_age as i64) >= now, CustomError::StalePrice);
+    require!(
+        (market.resolve_from as i64..=market.resolve_to as i64).contains(&publish_time),
+        CustomError::PriceOutsideResolveWindow
+    );
+    require!(
+        publish_time > market.last_ambiguous_publish_time as i64,
+        CustomError::PriceNotAfterAmbiguousPrice
+    );
+    Ok(())
+}
+
+/// Voids a market that was not resolved before the end of its resolve window
+///
+/// Anyone can void the market once it can no longer be resolved: after `resolve_to`, plus the
+/// maximum price age during which a price published at the end of the window can still be
+/// submitted. A market settled on a time-weighted average price with enough observations
+/// can always be resolved, so it is never voided here. Trading and redemption are disabled,
+/// traders get their cost basis back with `refund_outcome` and the creator reclaims the
+/// remaining subsidy with `reclaim_subsidy`.
+///
+/// # Arguments
+///
+/// * `ctx` - VoidMarket context containing required accounts
+///
+/// # Errors
+///
+/// Returns error if the market is already resolved, the program or market is paused
+/// or the market can still be resolved
+pub fn void_market(ctx: Context<VoidMarket>) -> Result<()> {
+    require!(!ctx.accounts.market.is_resolved, CustomError::MarketAlreadyResolved);
+    check_not_paused(&ctx.accounts.config, &ctx.accounts.market)?;
+    let now = Clock::get()?.unix_timestamp;
+    check_voidable(&ctx.accounts.market, now)?;
+
+    ctx.accounts.market.is_resolved = true;
+    ctx.accounts.market.is_voided = true;
+
+    emit_cpi!(MarketVoided {
+        market: ctx.accounts.market.key(),
+        voided_at: now,
+    });
+
+    Ok(())
+}
+
+/// Checks that a market can no longer be resolved, so that it can be voided
+///
+/// # Arguments
+///
+/// * `market` - Market to void
+/// * `now` - Current unix timestamp in seconds
+///
+/// # Errors
+///
+/// Returns error if a price published within the resolve window can still resolve the market,
+/// or enough prices were observed to settle it on their time-weighted average
+fn check_voidable(market: &Market, now: i64) -> Result<()> {
+    let resolvable_until = market.resolve_to.saturating_add(market.max_price_age);
+    require!(now > resolvable_until as i64, CustomError::MarketResolvable);
+    require!(
+        market.settlement_mode != SettlementMode::Twap || market.twap.observations < MIN_TWAP_OBSERVATIONS,
+        CustomError::MarketResolvable
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::market::test_market;
+
+    #[test]
+    fn price_must_be_recent_and_within_resolve_window() {
+        let mut market = test_market();
+
+        assert!(check_publish_time(&market, 1_000, 1_060).is_ok());
+        assert!(check_publish_time(&market, 2_000, 2_000).is_ok());
+        assert_eq!(check_publish_time(&market, 1_000, 1_061).unwrap_err(), CustomError::StalePrice.into());
+        // A price published within the window cannot be submitted long after it
+        assert_eq!(check_publish_time(&market, 1_500, 5_000).unwrap_err(), CustomError::StalePrice.into());
+        assert_eq!(
+            check_publish_time(&market, 999, 1_000).unwrap_err(),
+            CustomError::PriceOutsideResolveWindow.into()
+        );
+        assert_eq!(
+            check_publish_time(&market, 2_001, 2_001).unwrap_err(),
+            CustomError::PriceOutsideResolveWindow.into()
+        );
+
+        market.last_ambiguous_publish_time = 1_500;
+        assert_eq!(
+            check_publish_time(&market, 1_500, 1_500).unwrap_err(),
+            CustomError::PriceNotAfterAmbiguousPrice.into()
+        );
+        assert!(check_publish_time(&market, 1_501, 1_501).is_ok());
+    }
+
+    #[test]
+    fn market_is_voidable_only_once_it_cannot_be_resolved() {
+        let mut market = test_market();
+
+        // A price published at the end of the resolve window is accepted until it is too old
+        for now in 2_000..=2_060 {
+            assert!(check_publish_time(&market, 2_000, now).is_ok());
+            assert_eq!(check_voidable(&market, now).unwrap_err(), CustomError::MarketResolvable.into());
+        }
+        assert!(check_publish_time(&market, 2_000, 2_061).is_err());
+        assert!(check_voidable(&market, 2_061).is_ok());
+
+        market.settlement_mode = SettlementMode::Twap;
+        market.twap.observations = MIN_TWAP_OBSERVATIONS;
+        assert_eq!(check_voidable(&market, 3_000).unwrap_err(), CustomError::MarketResolvable.into());
+        market.twap.observations = MIN_TWAP_OBSERVATIONS - 1;
+        assert!(check_voidable(&market, 3_000).is_ok());
+    }
+
+    #[test]
+    fn price_is_ambiguous_within_k_conf_of_a_threshold() {
+        let thresholds = [20_050, 21_000];
+
+        // k = 2: ambiguous strictly within 2 * conf of any threshold
+        assert!(is_ambiguous(&thresholds, 20_031, 10, 20_000).unwrap());
+        assert!(is_ambiguous(&thresholds, 20_069, 10, 20_000).unwrap());
+        assert!(is_ambiguous(&thresholds, 20_990, 10, 20_000).unwrap());
+        assert!(!is_ambiguous(&thresholds, 20_030, 10, 20_000).unwrap());
+        assert!(!is_ambiguous(&thresholds, 20_070, 10, 20_000).unwrap());
+        assert!(!is_ambiguous(&thresholds, 20_500, 10, 20_000).unwrap());
+
+        // Fractional multiples, and a zero multiplier or interval never makes a price ambiguous
+        assert!(is_ambiguous(&thresholds, 20_046, 10, 5_000).unwrap());
+        assert!(!is_ambiguous(&thresholds, 20_045, 10, 5_000).unwrap());
+        assert!(!is_ambiguous(&thresholds, 20_050, 10, 0).unwrap());
+        assert!(!is_ambiguous(&thresholds, 20_050, 0, 20_000).unwrap());
+
+        // Negative prices and thresholds
+        assert!(is_ambiguous(&[-100], -115, 10, 20_000).unwrap());
+        assert!(!is_ambiguous(&[-100], -120, 10, 20_000).unwrap());
+
+        assert_eq!(
+            is_ambiguous(&thresholds, 20_050, u128::MAX, 20_000).unwrap_err(),
+            CustomError::MathOverflow.into()
+        );
+    }
+
+    #[test]
+    fn winning_outcome_counts_the_thresholds_below_the_price() {
+        let thresholds = [20_000, 20_050, 20_100];
+
+        assert_eq!(winning_outcome(PriceCondition::Thresholds, &thresholds, 19_999).unwrap(), 0);
+        // A price at a threshold is not above it
+        assert_eq!(winning_outcome(PriceCondition::Thresholds, &thresholds, 20_000).unwrap(), 0);
+        assert_eq!(winning_outcome(PriceCondition::Thresholds, &thresholds, 20_001).unwrap(), 1);
+        assert_eq!(winning_outcome(PriceCondition::Thresholds, &thresholds, 20_050).unwrap(), 1);
+        assert_eq!(winning_outcome(PriceCondition::Thresholds, &thresholds, 20_099).unwrap(), 2);
+        assert_eq!(winning_outcome(PriceCondition::Thresholds, &thresholds, 20_101).unwrap(), 3);
+        assert_eq!(winning_outcome(PriceCondition::Thresholds, &[-100], -150).unwrap(), 0);
+        assert_eq!(winning_outcome(PriceCondition::Thresholds, &[-100], -50).unwrap(), 1);
+    }
+
+    #[test]
+    fn outcome_0_wins_if_the_price_condition_holds() {
+        // (condition, below, at, above the threshold 200.50)
+        let cases = [
+            (PriceCondition::GreaterThan, 1, 1, 0),
+            (PriceCondition::GreaterOrEqual, 1, 0, 0),
+            (PriceCondition::LessThan, 0, 1, 1),
+            (PriceCondition::LessOrEqual, 0, 0, 1),
+        ];
+        for (condition, below, at, above) in cases {
+            assert_eq!(winning_outcome(condition, &[20_050], 20_049).unwrap(), below);
+            assert_eq!(winning_outcome(condition, &[20_050], 20_050).unwrap(), at);
+            assert_eq!(winning_outcome(condition, &[20_050], 20_051).unwrap(), above);
+        }
+
+        // The bracket includes its low and excludes its high threshold
+        let bracket = [20_000, 20_100];
+        assert_eq!(winning_outcome(PriceCondition::Inside, &bracket, 19_999).unwrap(), 1);
+        assert_eq!(winning_outcome(PriceCondition::Inside, &bracket, 20_000).unwrap(), 0);
+        assert_eq!(winning_outcome(PriceCondition::Inside, &bracket, 20_099).unwrap(), 0);
+        assert_eq!(winning_outcome(PriceCondition::Inside, &bracket, 20_100).unwrap(), 1);
+    }
+
+    #[test]
+    fn price_condition_must_match_the_number_of_thresholds() {
+        for condition in [
+            PriceCondition::GreaterThan,
+            PriceCondition::GreaterOrEqual,
+            PriceCondition::LessThan,
+            PriceCondition::LessOrEqual,
+        ] {
+            for thresholds in [&[][..], &[20_000, 20_100][..]] {
+                assert_eq!(
+                    winning_outcome(condition, thresholds, 20_050).unwrap_err(),
+                    CustomError::InvalidPriceCondition.into()
+                );
+            }
+        }
+        for thresholds in [&[20_000][..], &[20_000, 20_050, 20_100][..]] {
+            assert_eq!(
+                winning_outcome(PriceCondition::Inside, thresholds, 20_050).unwrap_err(),
+                CustomError::InvalidPriceCondition.into()
+            );
+        }
+    }
+
+    #[test]
+    fn confidence_interval_must_be_within_max_conf_bps_of_the_price() {
+        let price = |price: i128, conf: u128| OraclePrice {
+            price,
+            conf,
+            exponent: -2,
+            publish_time: 1_000,
+        };
+
+        // 100 bps of the price
+        assert!(check_confidence(&price(20_000, 200), 100).is_ok());
+        assert_eq!(
+            check_confidence(&price(20_000, 201), 100).unwrap_err(),
+            CustomError::ConfidenceTooWide.into()
+        );
+        // The ratio is taken on the absolute price
+        assert!(check_confidence(&price(-20_000, 200), 100).is_ok());
+        assert_eq!(check_confidence(&price(0, 1), 10_000).unwrap_err(), CustomError::ConfidenceTooWide.into());
+        assert!(check_confidence(&price(0, 0), 0).is_ok());
+    }
+}
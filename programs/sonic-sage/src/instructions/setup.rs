@@ -1,6 +1,7 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::pubkey::Pubkey;
-use anchor_spl::token::{Mint, TokenAccount, Token};
+use crate::errors::*;
+use crate::state::config::Config;
 use crate::state::metadata::Metadata;
 
 /// Context accounts for setting up the program
@@ -8,7 +9,7 @@ use crate::state::metadata::Metadata;
 pub struct Setup<'info> {
     #[account(
         init,
-        payer = signer,
+        payer = admin,
         space = 8 + 8,
         seeds = [b"metadata"],
         bump
@@ -16,27 +17,22 @@ pub struct Setup<'info> {
     pub metadata: Account<'info, Metadata>,
 
     #[account(
-        init,
-        payer = signer,
-        token::mint = mint,
-        token::authority = token_account,
-        seeds = [b"token"],
-        bump
+        seeds = [b"config"],
+        bump,
+        has_one = admin @ CustomError::Unauthorized
     )]
-    pub token_account: Account<'info, TokenAccount>,
-
-    #[account(mut)]
-    pub mint: Account<'info, Mint>,
-
-    pub token_program: Program<'info, Token>,
+    pub config: Account<'info, Config>,
 
     #[account(mut)]
-    pub signer: Signer<'info>,
+    pub admin: Signer<'info>,
 
     pub system_program: Program<'info, System>,
 }
 
 /// Initializes the program metadata
+///
+/// Only the admin of the configuration can set up the metadata, so the
+/// configuration must be initialized first.
 /// 
 /// # Arguments
 /// 
@@ -1,12 +1,19 @@
 pub mod errors;
+pub mod events;
 pub mod instructions;
+pub mod math;
+pub mod oracle;
 pub mod state;
+pub mod transfer;
 
 use anchor_lang::prelude::*;
 
 pub use errors::*;
+pub use events::*;
+#[allow(ambiguous_glob_reexports)]
 pub use instructions::*;
 pub use state::*;
+use state::market::{ConfidencePolicy, OracleKind, OracleSource, PriceCondition, SettlementMode};
 
 // Program's public key
 declare_id!("7JoThMj3epYFva6TBDQVDGDrn92Uncwj8vB11SJQUUPn");
@@ -15,7 +22,7 @@ declare_id!("7JoThMj3epYFva6TBDQVDGDrn92Uncwj8vB11SJQUUPn");
 mod sonic_sage {
     use super::*;
 
-    /// Initializes the metadata for the program.
+    /// Initializes the metadata for the program. Must be signed by the admin of the configuration.
     ///
     /// # Arguments
     ///
@@ -24,25 +31,294 @@ mod sonic_sage {
         setup::setup_metadata(ctx)
     }
 
+    /// Initializes the global configuration with the signer as admin. Must be signed by the
+    /// upgrade authority of the program.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context containing the necessary accounts and information to set up the configuration.
+    /// * `protocol_fee_bps` - The fee charged by the protocol on every trade, in basis points.
+    pub fn initialize_config(ctx: Context<InitializeConfig>, protocol_fee_bps: u16) -> Result<()> {
+        admin::initialize_config(ctx, protocol_fee_bps)
+    }
+
+    /// Updates the fee charged by the protocol on every trade.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context containing the configuration and its admin.
+    /// * `protocol_fee_bps` - The fee charged by the protocol on every trade, in basis points.
+    pub fn set_protocol_fee(ctx: Context<UpdateConfig>, protocol_fee_bps: u16) -> Result<()> {
+        admin::set_protocol_fee(ctx, protocol_fee_bps)
+    }
+
+    /// Updates the maximum fee a market creator can charge on every trade.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context containing the configuration and its admin.
+    /// * `max_creator_fee_bps` - The maximum creator fee, in basis points.
+    pub fn set_max_creator_fee(ctx: Context<UpdateConfig>, max_creator_fee_bps: u16) -> Result<()> {
+        admin::set_max_creator_fee(ctx, max_creator_fee_bps)
+    }
+
+    /// Updates the upper bound on the maximum age of an oracle price that market creators can set.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context containing the configuration and its admin.
+    /// * `max_price_age` - The upper bound on the maximum price age, in seconds.
+    pub fn set_max_price_age(ctx: Context<UpdateConfig>, max_price_age: u64) -> Result<()> {
+        admin::set_max_price_age(ctx, max_price_age)
+    }
+
+    /// Proposes a new admin, who must accept the admin authority before it is transferred.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context containing the configuration and its admin.
+    /// * `new_admin` - The public key of the proposed admin.
+    pub fn propose_admin(ctx: Context<UpdateConfig>, new_admin: Pubkey) -> Result<()> {
+        admin::propose_admin(ctx, new_admin)
+    }
+
+    /// Transfers the admin authority to the pending admin.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context containing the configuration and the pending admin.
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        admin::accept_admin(ctx)
+    }
+
+    /// Pauses or unpauses market creation, trading, resolution and redemption on all markets.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context containing the configuration and its admin.
+    /// * `is_paused` - Whether the program is paused.
+    pub fn set_paused(ctx: Context<UpdateConfig>, is_paused: bool) -> Result<()> {
+        admin::set_paused(ctx, is_paused)
+    }
+
+    /// Pauses or unpauses trading, resolution and redemption on a single market.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context containing the configuration, its admin and the market.
+    /// * `is_paused` - Whether the market is paused.
+    pub fn set_market_paused(ctx: Context<SetMarketPaused>, is_paused: bool) -> Result<()> {
+        admin::set_market_paused(ctx, is_paused)
+    }
+
+    /// Adds a price feed of an oracle provider to the feeds markets can be created on.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context containing the configuration and its admin.
+    /// * `oracle_kind` - The oracle provider of the price feed.
+    /// * `price_feed_id` - The hex encoded price feed id.
+    pub fn add_allowed_price_feed(
+        ctx: Context<UpdateConfig>,
+        oracle_kind: OracleKind,
+        price_feed_id: String,
+    ) -> Result<()> {
+        admin::add_allowed_price_feed(ctx, oracle_kind, price_feed_id)
+    }
+
+    /// Removes a price feed of an oracle provider from the feeds markets can be created on.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context containing the configuration and its admin.
+    /// * `oracle_kind` - The oracle provider of the price feed.
+    /// * `price_feed_id` - The hex encoded price feed id.
+    pub fn remove_allowed_price_feed(
+        ctx: Context<UpdateConfig>,
+        oracle_kind: OracleKind,
+        price_feed_id: String,
+    ) -> Result<()> {
+        admin::remove_allowed_price_feed(ctx, oracle_kind, price_feed_id)
+    }
+
+    /// Adds a collateral mint to the allowlist of mints markets can be created with.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context containing the configuration, its admin and the mint to add.
+    pub fn add_collateral_mint(ctx: Context<AddCollateralMint>) -> Result<()> {
+        admin::add_collateral_mint(ctx)
+    }
+
+    /// Enables or disables the creation of new markets with a collateral mint.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context containing the configuration, its admin and the collateral mint.
+    /// * `is_enabled` - Whether new markets can be created with the collateral mint.
+    pub fn set_collateral_mint_enabled(ctx: Context<UpdateCollateralMint>, is_enabled: bool) -> Result<()> {
+        admin::set_collateral_mint_enabled(ctx, is_enabled)
+    }
+
+    /// Sets the price of a mock price feed, for testing markets with the mock oracle on localnet.
+    /// Only available in builds with the `localnet` feature.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context containing the configuration, its admin and the mock oracle.
+    /// * `feed_id` - The ID of the mocked price feed.
+    /// * `price` - The price, standing for `price * 10^exponent`.
+    /// * `conf` - The confidence interval of the price.
+    /// * `exponent` - The exponent of the price and confidence interval.
+    /// * `publish_time` - The unix timestamp in seconds when the price was published.
+    pub fn set_mock_price(
+        ctx: Context<SetMockPrice>,
+        feed_id: [u8; 32],
+        price: i64,
+        conf: u64,
+        exponent: i32,
+        publish_time: i64,
+    ) -> Result<()> {
+        admin::set_mock_price(ctx, feed_id, price, conf, exponent, publish_time)
+    }
+
     /// Creates a new prediction market with initial liquidity subsidy.
     /// 
     /// # Arguments
     /// 
     /// * `ctx` - The context containing the necessary accounts and information to create a market.
-    /// * `price` - The predicted price for the market.
-    /// * `price_feed_id` - The identifier for the Pyth price feed used for resolution.
-    /// * `resolve_from` - The timestamp from which the price feed data is considered valid.
-    /// * `resolve_to` - The timestamp until which the price feed data is considered valid.
+    /// * `price_thresholds` - The ascending price thresholds separating the outcomes.
+    /// * `price_exponent` - The exponent of the price thresholds.
+    /// * `condition` - The condition on the price deciding the winning outcome.
+    /// * `outcome_labels` - The human-readable label of every outcome.
+    /// * `oracle_kind` - The oracle provider of the price feed.
+    /// * `price_feed_id` - The hex encoded identifier of the price feed used for resolution.
+    /// * `resolve_from` - The unix timestamp in seconds from which the price feed data is considered valid.
+    /// * `resolve_to` - The unix timestamp in seconds until which the price feed data is considered valid.
     /// * `subsidy_amount` - The amount of liquidity subsidy to provide for the market.
+    /// * `liquidity_b` - The LMSR liquidity parameter of the market.
+    /// * `creator_fee_bps` - The fee charged by the creator on every trade, in basis points.
+    /// * `trading_opens_at` - The optional unix timestamp in seconds when trading opens, immediately if not set.
+    /// * `trading_closes_at` - The optional unix timestamp in seconds when trading closes, `resolve_from` if not set.
+    /// * `confidence_policy` - The policy applied to the confidence interval of the oracle price at resolution.
+    /// * `max_price_age` - The maximum age of the oracle price at resolution, in seconds.
+    /// * `allow_partial_verification` - Whether partially verified oracle price updates are accepted at resolution.
+    /// * `additional_oracles` - The price feeds of other oracle providers the market is also resolved from, at most two.
+    /// * `max_oracle_deviation_bps` - The maximum spread of the oracle prices, in basis points of their median.
+    /// * `settlement_mode` - The price the market is settled on, the spot, moving average or time-weighted average price.
+    #[allow(clippy::too_many_arguments)]
     pub fn create_market(
         ctx: Context<CreateMarket>,
-        price: f64,
+        price_thresholds: Vec<i64>,
+        price_exponent: i32,
+        condition: PriceCondition,
+        outcome_labels: Vec<String>,
+        oracle_kind: OracleKind,
         price_feed_id: String,
         resolve_from: u64,
         resolve_to: u64,
-        subsidy_amount: u64
+        subsidy_amount: u64,
+        liquidity_b: u64,
+        creator_fee_bps: u16,
+        trading_opens_at: Option<u64>,
+        trading_closes_at: Option<u64>,
+        confidence_policy: ConfidencePolicy,
+        max_price_age: u64,
+        allow_partial_verification: bool,
+        additional_oracles: Vec<OracleSource>,
+        max_oracle_deviation_bps: u16,
+        settlement_mode: SettlementMode
     ) -> Result<()> {
-        create::create_market(ctx, price, price_feed_id, resolve_from, resolve_to, subsidy_amount)
+        create::create_market(
+            ctx,
+            price_thresholds,
+            price_exponent,
+            condition,
+            outcome_labels,
+            oracle_kind,
+            price_feed_id,
+            resolve_from,
+            resolve_to,
+            subsidy_amount,
+            liquidity_b,
+            creator_fee_bps,
+            trading_opens_at,
+            trading_closes_at,
+            confidence_policy,
+            max_price_age,
+            allow_partial_verification,
+            additional_oracles,
+            max_oracle_deviation_bps,
+            settlement_mode,
+        )
+    }
+
+    /// Creates a new scalar prediction market with initial liquidity subsidy.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context containing the necessary accounts and information to create a market.
+    /// * `lower` - The lower bound of the price range, at or below which LONG shares pay nothing.
+    /// * `upper` - The upper bound of the price range, at or above which LONG shares pay in full.
+    /// * `price_exponent` - The exponent of the bounds of the price range.
+    /// * `oracle_kind` - The oracle provider of the price feed.
+    /// * `price_feed_id` - The hex encoded identifier of the price feed used for resolution.
+    /// * `resolve_from` - The unix timestamp in seconds from which the price feed data is considered valid.
+    /// * `resolve_to` - The unix timestamp in seconds until which the price feed data is considered valid.
+    /// * `subsidy_amount` - The amount of liquidity subsidy to provide for the market.
+    /// * `liquidity_b` - The LMSR liquidity parameter of the market.
+    /// * `creator_fee_bps` - The fee charged by the creator on every trade, in basis points.
+    /// * `trading_opens_at` - The optional unix timestamp in seconds when trading opens, immediately if not set.
+    /// * `trading_closes_at` - The optional unix timestamp in seconds when trading closes, `resolve_from` if not set.
+    /// * `confidence_policy` - The policy applied to the confidence interval of the oracle price at resolution.
+    /// * `max_price_age` - The maximum age of the oracle price at resolution, in seconds.
+    /// * `allow_partial_verification` - Whether partially verified oracle price updates are accepted at resolution.
+    /// * `additional_oracles` - The price feeds of other oracle providers the market is also resolved from, at most two.
+    /// * `max_oracle_deviation_bps` - The maximum spread of the oracle prices, in basis points of their median.
+    /// * `settlement_mode` - The price the market is settled on, the spot, moving average or time-weighted average price.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_scalar_market(
+        ctx: Context<CreateMarket>,
+        lower: i64,
+        upper: i64,
+        price_exponent: i32,
+        oracle_kind: OracleKind,
+        price_feed_id: String,
+        resolve_from: u64,
+        resolve_to: u64,
+        subsidy_amount: u64,
+        liquidity_b: u64,
+        creator_fee_bps: u16,
+        trading_opens_at: Option<u64>,
+        trading_closes_at: Option<u64>,
+        confidence_policy: ConfidencePolicy,
+        max_price_age: u64,
+        allow_partial_verification: bool,
+        additional_oracles: Vec<OracleSource>,
+        max_oracle_deviation_bps: u16,
+        settlement_mode: SettlementMode
+    ) -> Result<()> {
+        create::create_scalar_market(
+            ctx,
+            lower,
+            upper,
+            price_exponent,
+            oracle_kind,
+            price_feed_id,
+            resolve_from,
+            resolve_to,
+            subsidy_amount,
+            liquidity_b,
+            creator_fee_bps,
+            trading_opens_at,
+            trading_closes_at,
+            confidence_policy,
+            max_price_age,
+            allow_partial_verification,
+            additional_oracles,
+            max_oracle_deviation_bps,
+            settlement_mode,
+        )
     }
 
     /// Buys outcome shares for a specific outcome in a market.
@@ -52,12 +328,35 @@ mod sonic_sage {
     /// * `ctx` - The context containing the necessary accounts and information to buy outcome tokens.
     /// * `outcome_idx` - The outcome idx for which to buy shares.
     /// * `num_shares` - The number of shares to buy.
+    /// * `max_cost` - The maximum amount of tokens to pay for the shares.
+    /// * `expires_at` - The optional timestamp after which the trade is rejected.
     pub fn buy_outcome(
         ctx: Context<BuySellOutcome>,
         outcome_idx: u8,
-        num_shares: u64
+        num_shares: u64,
+        max_cost: u64,
+        expires_at: Option<u64>
     ) -> Result<()> { 
-        buy_sell::buy_outcome(ctx, outcome_idx, num_shares)
+        buy_sell::buy_outcome(ctx, outcome_idx, num_shares, max_cost, expires_at)
+    }
+
+    /// Buys as many outcome shares as a given amount of tokens pays for.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `ctx` - The context containing the necessary accounts and information to buy outcome tokens.
+    /// * `outcome_idx` - The outcome idx for which to buy shares.
+    /// * `collateral_amount` - The maximum amount of tokens to spend.
+    /// * `min_shares` - The minimum number of shares to receive.
+    /// * `expires_at` - The optional timestamp after which the trade is rejected.
+    pub fn buy_outcome_exact_in(
+        ctx: Context<BuySellOutcome>,
+        outcome_idx: u8,
+        collateral_amount: u64,
+        min_shares: u64,
+        expires_at: Option<u64>
+    ) -> Result<()> {
+        buy_sell::buy_outcome_exact_in(ctx, outcome_idx, collateral_amount, min_shares, expires_at)
     }
 
     /// Sells outcome shares for a specific outcome in a market.
@@ -67,24 +366,75 @@ mod sonic_sage {
     /// * `ctx` - The context containing the necessary accounts and information to sell outcome tokens.
     /// * `outcome_idx` - The outcome idx for which to sell shares.
     /// * `num_shares` - The number of shares to sell.
+    /// * `min_proceeds` - The minimum amount of tokens to receive for the shares.
+    /// * `expires_at` - The optional timestamp after which the trade is rejected.
     pub fn sell_outcome(
         ctx: Context<BuySellOutcome>,
         outcome_idx: u8,
-        num_shares: u64
+        num_shares: u64,
+        min_proceeds: u64,
+        expires_at: Option<u64>
     ) -> Result<()> { 
-        buy_sell::sell_outcome(ctx, outcome_idx, num_shares)
+        buy_sell::sell_outcome(ctx, outcome_idx, num_shares, min_proceeds, expires_at)
+    }
+
+    /// Sells the fewest outcome shares that pay out a given amount of tokens.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `ctx` - The context containing the necessary accounts and information to sell outcome tokens.
+    /// * `outcome_idx` - The outcome idx for which to sell shares.
+    /// * `proceeds_amount` - The amount of tokens to receive.
+    /// * `max_shares` - The maximum number of shares to sell.
+    /// * `expires_at` - The optional timestamp after which the trade is rejected.
+    pub fn sell_outcome_exact_out(
+        ctx: Context<BuySellOutcome>,
+        outcome_idx: u8,
+        proceeds_amount: u64,
+        max_shares: u64,
+        expires_at: Option<u64>
+    ) -> Result<()> {
+        buy_sell::sell_outcome_exact_out(ctx, outcome_idx, proceeds_amount, max_shares, expires_at)
     }
 
-    /// Resolves a market by comparing the target price to the actual price from oracle.
+    /// Quotes a hypothetical trade on a market without changing any state.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `ctx` - The context containing the market to quote the trade on.
+    /// * `side` - Whether to quote a buy or a sell.
+    /// * `outcome_idx` - The outcome idx to trade.
+    /// * `num_shares` - The number of shares to trade.
+    /// * `expires_at` - The optional timestamp after which the trade is rejected.
+    pub fn quote_trade(
+        ctx: Context<QuoteTrade>,
+        side: TradeSide,
+        outcome_idx: u8,
+        num_shares: u64,
+        expires_at: Option<u64>
+    ) -> Result<TradeQuote> {
+        quote::quote_trade(ctx, side, outcome_idx, num_shares, expires_at)
+    }
+
+    /// Resolves a market by comparing the price thresholds to the actual price from oracle.
     /// 
     /// # Arguments
     /// 
     /// * `ctx` - The context containing the necessary accounts and information to resolve a market.
-    pub fn resolve_market(ctx: Context<ResolveMarket>) -> Result<()> {
+    pub fn resolve_market<'info>(ctx: Context<'_, '_, 'info, 'info, ResolveMarket<'info>>) -> Result<()> {
         resolve::resolve_market(ctx)
     }
 
-    /// Redeems winning outcome shares after market resolution.
+    /// Records an observation of the oracle prices of a market settled on a time-weighted average price.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context containing the market and its oracle accounts.
+    pub fn record_observation<'info>(ctx: Context<'_, '_, 'info, 'info, RecordObservation<'info>>) -> Result<()> {
+        resolve::record_observation(ctx)
+    }
+
+    /// Redeems winning outcome shares after market resolution and closes the position.
     /// 
     /// # Arguments
     /// 
@@ -92,4 +442,76 @@ mod sonic_sage {
     pub fn redeem_outcome(ctx: Context<RedeemOutcome>) -> Result<()> {
         redeem::redeem_outcome(ctx)
     }
+
+    /// Closes a position without a payout, returning its rent to the user.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `ctx` - The context containing the necessary accounts and information to close a position.
+    pub fn close_position(ctx: Context<ClosePosition>) -> Result<()> {
+        redeem::close_position(ctx)
+    }
+
+    /// Voids a market that was not resolved and can no longer be resolved.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `ctx` - The context containing the necessary accounts and information to void a market.
+    pub fn void_market(ctx: Context<VoidMarket>) -> Result<()> {
+        resolve::void_market(ctx)
+    }
+
+    /// Refunds the cost basis of a position in a voided market.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `ctx` - The context containing the necessary accounts and information to refund outcome shares.
+    pub fn refund_outcome(ctx: Context<RefundOutcome>) -> Result<()> {
+        refund::refund_outcome(ctx)
+    }
+
+    /// Reclaims the remaining subsidy of a voided market for its creator.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `ctx` - The context containing the necessary accounts and information to reclaim the subsidy.
+    pub fn reclaim_subsidy(ctx: Context<ReclaimSubsidy>) -> Result<()> {
+        refund::reclaim_subsidy(ctx)
+    }
+
+    /// Claims the fees accrued by a market for its creator.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `ctx` - The context containing the necessary accounts and information to claim creator fees.
+    pub fn claim_creator_fees(ctx: Context<ClaimCreatorFees>) -> Result<()> {
+        fees::claim_creator_fees(ctx)
+    }
+
+    /// Claims the fees accrued by the protocol for a mint.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `ctx` - The context containing the necessary accounts and information to claim protocol fees.
+    pub fn claim_protocol_fees(ctx: Context<ClaimProtocolFees>) -> Result<()> {
+        fees::claim_protocol_fees(ctx)
+    }
+
+    /// Migrates a legacy market to the current market layout and its own vault.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `ctx` - The context containing the necessary accounts and information to migrate the market.
+    pub fn migrate_market(ctx: Context<MigrateMarket>) -> Result<()> {
+        migrate::migrate_market(ctx)
+    }
+
+    /// Migrates a position in a migrated legacy market to the current position layout.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `ctx` - The context containing the necessary accounts and information to migrate the position.
+    pub fn migrate_position(ctx: Context<MigratePosition>) -> Result<()> {
+        migrate::migrate_position(ctx)
+    }
 }
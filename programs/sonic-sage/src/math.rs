@@ -0,0 +1,445 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::*;
+
+/// Scale of the fixed-point numbers used by the LMSR math (18 decimals).
+/// A value `x` represents the real number `x / SCALE`.
+pub const SCALE: u128 = 1_000_000_000_000_000_000;
+
+/// ln(2) scaled by `SCALE`, rounded down
+const LN_2: u128 = 693_147_180_559_945_309;
+
+/// Number of decimals of `SCALE`
+pub const SCALE_DECIMALS: u8 = 18;
+
+/// Upper bound (in units of `1 / SCALE`) on the approximation error of `exp_neg` and `ln`.
+/// Results are shifted by this amount in the requested rounding direction so that
+/// rounding up never underestimates and rounding down never overestimates the exact value.
+const MAX_ERROR: u128 = 128;
+
+/// Direction in which an inexact fixed-point result is rounded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    Up,
+    Down,
+}
+
+/// Calculates e^(-x) for a non-negative fixed-point `x`
+///
+/// The argument is reduced as x = k * ln(2) + r with r in [0, ln(2)), so that
+/// e^(-x) = 2^(-k) * e^(-r), and e^(-r) is evaluated with its Taylor series.
+///
+/// # Arguments
+///
+/// * `x` - Exponent to negate, scaled by `SCALE`
+/// * `rounding` - Rounding direction of the result
+///
+/// # Returns
+///
+/// e^(-x) scaled by `SCALE`, always in [0, SCALE]
+pub fn exp_neg(x: u128, rounding: Rounding) -> u128 {
+    if x == 0 {
+        return SCALE;
+    }
+
+    let k = x / LN_2;
+    let approx = if k >= 128 {
+        0
+    } else {
+        let r = x - k * LN_2;
+
+        // e^(-r) = sum((-r)^n / n!), terms are strictly decreasing since r < 1
+        let mut sum = SCALE;
+        let mut term = SCALE;
+        let mut n = 1;
+        loop {
+            term = term * r / (SCALE * n);
+            if term == 0 {
+                break;
+            }
+            if n % 2 == 1 {
+                sum -= term;
+            } else {
+                sum += term;
+            }
+            n += 1;
+        }
+
+        sum >> k
+    };
+
+    match rounding {
+        Rounding::Up => (approx + MAX_ERROR).min(SCALE),
+        Rounding::Down => approx.saturating_sub(MAX_ERROR),
+    }
+}
+
+/// Calculates ln(x) for a fixed-point `x >= 1`
+///
+/// The argument is reduced as x = 2^k * m with m in [1, 2), so that
+/// ln(x) = k * ln(2) + ln(m), and ln(m) is evaluated with the series
+/// ln(m) = 2 * atanh(z) = 2 * sum(z^(2n+1) / (2n+1)) where z = (m - 1) / (m + 1).
+///
+/// # Arguments
+///
+/// * `x` - Value to take the logarithm of, scaled by `SCALE`
+/// * `rounding` - Rounding direction of the result
+///
+/// # Returns
+///
+/// ln(x) scaled by `SCALE`
+///
+/// # Errors
+///
+/// Returns error if `x` is smaller than one
+pub fn ln(x: u128, rounding: Rounding) -> Result<u128> {
+    require!(x >= SCALE, CustomError::MathOverflow);
+
+    let k = (x / SCALE).ilog2();
+    let m = x >> k;
+
+    // z <= 1/3, so every term is at least 9 times smaller than the previous one
+    let z = (m - SCALE) * SCALE / (m + SCALE);
+    let z_squared = z * z / SCALE;
+    let mut sum = z;
+    let mut term = z;
+    let mut n = 1;
+    loop {
+        term = term * z_squared / SCALE;
+        if term == 0 {
+            break;
+        }
+        sum += term / (2 * n + 1);
+        n += 1;
+    }
+
+    let approx = k as u128 * LN_2 + 2 * sum;
+
+    Ok(match rounding {
+        Rounding::Up => approx + MAX_ERROR,
+        Rounding::Down => approx.saturating_sub(MAX_ERROR),
+    })
+}
+
+/// Calculates the LMSR cost function
+///
+/// Implements C(q) = b * ln(sum_i(exp(q_i/b))) in its numerically stable
+/// log-sum-exp form C(q) = max(q) + b * ln(sum_i(exp((q_i - max(q))/b))),
+/// so every exponent is non-positive and the sum lies in [1, n].
+///
+/// # Arguments
+///
+/// * `quantities` - Number of shares of every outcome
+/// * `b` - Liquidity parameter of the market
+/// * `rounding` - Rounding direction of the result
+///
+/// # Returns
+///
+/// The cost in shares, scaled by `SCALE`
+///
+/// # Errors
+///
+/// Returns error if `b` is zero or the cost does not fit into 128 bits
+pub fn lmsr_cost(quantities: &[u64], b: u64, rounding: Rounding) -> Result<u128> {
+    require!(b > 0, CustomError::MathOverflow);
+    let max_q = quantities.iter().copied().max().unwrap_or_default();
+
+    let mut sum: u128 = 0;
+    for q in quantities {
+        let x = ((max_q - q) as u128 * SCALE) / b as u128;
+        sum = sum
+            .checked_add(exp_neg(x, rounding))
+            .ok_or(CustomError::MathOverflow)?;
+    }
+
+    let ln_sum = ln(sum, rounding)?;
+    (max_q as u128)
+        .checked_mul(SCALE)
+        .and_then(|base| base.checked_add((b as u128).checked_mul(ln_sum)?))
+        .ok_or(error!(CustomError::MathOverflow))
+}
+
+/// Calculates the LMSR prices of every outcome
+///
+/// Calculates p_i = exp(q_i/b) / sum_j(exp(q_j/b)), using the same
+/// max-shifted exponents as `lmsr_cost`.
+///
+/// # Arguments
+///
+/// * `quantities` - Number of shares of every outcome
+/// * `b` - Liquidity parameter of the market
+///
+/// # Returns
+///
+/// The price of every outcome, scaled by `SCALE` and rounded down
+///
+/// # Errors
+///
+/// Returns error if `b` is zero
+pub fn lmsr_prices(quantities: &[u64], b: u64) -> Result<Vec<u64>> {
+    require!(b > 0, CustomError::MathOverflow);
+    let max_q = quantities.iter().copied().max().unwrap_or_default();
+
+    let exps: Vec<u128> = quantities
+        .iter()
+        .map(|q| exp_neg(((max_q - q) as u128 * SCALE) / b as u128, Rounding::Down))
+        .collect();
+    let sum: u128 = exps.iter().sum();
+
+    Ok(exps.iter().map(|e| (e * SCALE / sum) as u64).collect())
+}
+
+/// Inverts the LMSR cost function for a single outcome
+///
+/// Solves C(q) = `target_cost` for the quantity of outcome `outcome_idx` while the
+/// quantities of all other outcomes stay fixed, i.e.
+/// q_i = C - b * ln(1 / (1 - sum_{j != i}(exp((q_j - C)/b)))).
+/// The result is an approximation that callers refine against `lmsr_cost`.
+///
+/// # Arguments
+///
+/// * `quantities` - Number of shares of every outcome
+/// * `outcome_idx` - Index of the outcome whose quantity is solved for
+/// * `b` - Liquidity parameter of the market
+/// * `target_cost` - Cost to reach, scaled by `SCALE`
+///
+/// # Returns
+///
+/// The quantity of the outcome scaled by `SCALE`, or `None` if no
+/// non-negative quantity reaches `target_cost`
+pub fn lmsr_quantity_for_cost(
+    quantities: &[u64],
+    outcome_idx: usize,
+    b: u64,
+    target_cost: u128,
+) -> Option<u128> {
+    if b == 0 {
+        return None;
+    }
+
+    let mut sum: u128 = 0;
+    for (idx, q) in quantities.iter().enumerate() {
+        if idx == outcome_idx {
+            continue;
+        }
+        let q = *q as u128 * SCALE;
+        if target_cost <= q {
+            return None;
+        }
+        sum += exp_neg((target_cost - q) / b as u128, Rounding::Down);
+    }
+    if sum >= SCALE {
+        return None;
+    }
+
+    let ln_ratio = ln(SCALE * SCALE / (SCALE - sum), Rounding::Down).ok()?;
+    target_cost.checked_sub((b as u128).checked_mul(ln_ratio)?)
+}
+
+/// Converts an amount in base units of a mint into a fixed-point amount of whole tokens
+///
+/// # Arguments
+///
+/// * `amount` - Amount of tokens (with decimals)
+/// * `decimals` - Decimals of the mint
+///
+/// # Errors
+///
+/// Returns error if the mint has more decimals than `SCALE`
+pub fn from_token_amount(amount: u64, decimals: u8) -> Result<u128> {
+    require!(decimals <= SCALE_DECIMALS, CustomError::MathOverflow);
+    Ok(amount as u128 * 10u128.pow((SCALE_DECIMALS - decimals) as u32))
+}
+
+/// Converts a fixed-point amount of whole tokens into base units of a mint
+///
+/// # Arguments
+///
+/// * `value` - Amount of whole tokens, scaled by `SCALE`
+/// * `decimals` - Decimals of the mint
+/// * `rounding` - Rounding direction of the result
+///
+/// # Errors
+///
+/// Returns error if the mint has more decimals than `SCALE` or the amount does not fit into 64 bits
+pub fn to_token_amount(value: u128, decimals: u8, rounding: Rounding) -> Result<u64> {
+    require!(decimals <= SCALE_DECIMALS, CustomError::MathOverflow);
+    let divisor = 10u128.pow((SCALE_DECIMALS - decimals) as u32);
+    let amount = match rounding {
+        Rounding::Up => value.div_ceil(divisor),
+        Rounding::Down => value / divisor,
+    };
+
+    u64::try_from(amount).map_err(|_| error!(CustomError::MathOverflow))
+}
+
+/// Rescales a value `value * 10^exponent` to a lower or equal exponent
+///
+/// # Arguments
+///
+/// * `value` - Value to rescale
+/// * `exponent` - Exponent of the value
+/// * `to_exponent` - Exponent of the result, at most `exponent`
+///
+/// # Errors
+///
+/// Returns error if `to_exponent` is above `exponent` or the result overflows
+///
+/// # Returns
+///
+/// The value `result` such that `result * 10^to_exponent == value * 10^exponent`
+pub fn rescale(value: i128, exponent: i32, to_exponent: i32) -> Result<i128> {
+    let shift = exponent
+        .checked_sub(to_exponent)
+        .and_then(|shift| u32::try_from(shift).ok())
+        .ok_or(CustomError::MathOverflow)?;
+
+    Ok(10i128
+        .checked_pow(shift)
+        .and_then(|factor| value.checked_mul(factor))
+        .ok_or(CustomError::MathOverflow)?)
+}
+
+/// Calculates the median of values, the mean of the two middle values for an even count
+///
+/// # Arguments
+///
+/// * `values` - Values to take the median of, in any order
+///
+/// # Errors
+///
+/// Returns error if there are no values or the mean overflows
+///
+/// # Returns
+///
+/// The median, rounded down for an even count
+pub fn median(values: &[i128]) -> Result<i128> {
+    require!(!values.is_empty(), CustomError::MathOverflow);
+
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 1 {
+        return Ok(sorted[mid]);
+    }
+
+    Ok(sorted[mid - 1].checked_add(sorted[mid]).ok_or(CustomError::MathOverflow)?.div_euclid(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_f64(x: u128) -> f64 {
+        x as f64 / SCALE as f64
+    }
+
+    /// Reference LMSR cost in floating point
+    fn reference_cost(quantities: &[u64], b: u64) -> f64 {
+        let b = b as f64;
+        let max_q = quantities.iter().copied().max().unwrap() as f64;
+        let sum: f64 = quantities.iter().map(|q| ((*q as f64 - max_q) / b).exp()).sum();
+        max_q + b * sum.ln()
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        let error = (actual - expected).abs() / expected.abs().max(1.0);
+        assert!(error <= tolerance, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn exp_neg_matches_reference() {
+        for i in 0..2_000u128 {
+            let x = i * SCALE / 40;
+            let expected = (-to_f64(x)).exp();
+            assert_close(to_f64(exp_neg(x, Rounding::Down)), expected, 1e-12);
+            assert!(exp_neg(x, Rounding::Down) <= exp_neg(x, Rounding::Up));
+        }
+        assert_eq!(exp_neg(0, Rounding::Down), SCALE);
+        assert_eq!(exp_neg(100 * SCALE, Rounding::Down), 0);
+    }
+
+    #[test]
+    fn ln_matches_reference() {
+        for i in 0..2_000u128 {
+            let x = SCALE + i * SCALE / 100;
+            let expected = to_f64(x).ln();
+            assert_close(to_f64(ln(x, Rounding::Down).unwrap()), expected, 1e-12);
+            assert!(ln(x, Rounding::Down).unwrap() <= ln(x, Rounding::Up).unwrap());
+        }
+        assert!(ln(SCALE - 1, Rounding::Down).is_err());
+    }
+
+    #[test]
+    fn cost_matches_reference_across_share_range() {
+        for b in [1u64, 50, 1_000, 1_000_000] {
+            let mut q = 0u64;
+            while q < 1_000_000_000_000 {
+                for other in [0u64, q / 2, q, q.saturating_mul(3)] {
+                    let quantities = [q, other];
+                    let up = lmsr_cost(&quantities, b, Rounding::Up).unwrap();
+                    let down = lmsr_cost(&quantities, b, Rounding::Down).unwrap();
+                    assert!(down <= up);
+                    assert_close(to_f64(down), reference_cost(&quantities, b), 1e-12);
+                }
+                q = q * 3 + 1;
+            }
+        }
+    }
+
+    #[test]
+    fn prices_sum_to_one() {
+        let prices = lmsr_prices(&[100, 100], 50).unwrap();
+        assert_eq!(prices[0], prices[1]);
+        assert_close(to_f64(prices[0] as u128), 0.5, 1e-15);
+
+        let prices = lmsr_prices(&[150, 100], 50).unwrap();
+        let expected = 1f64.exp() / (1f64.exp() + 1.0);
+        assert_close(to_f64(prices[0] as u128), expected, 1e-12);
+        assert_close(to_f64((prices[0] + prices[1]) as u128), 1.0, 1e-15);
+    }
+
+    #[test]
+    fn quantity_for_cost_inverts_cost() {
+        for b in [50u64, 1_000, 1_000_000] {
+            for quantities in [[100u64, 100], [1_000, 10], [10, 5_000_000]] {
+                let cost = lmsr_cost(&quantities, b, Rounding::Down).unwrap();
+                for extra in [SCALE / 3, 25 * SCALE, 10_000 * SCALE] {
+                    let target = cost + extra;
+                    let q = to_f64(lmsr_quantity_for_cost(&quantities, 0, b, target).unwrap());
+                    let b = b as f64;
+                    let q_other = quantities[1] as f64;
+                    let max_q = q.max(q_other);
+                    let actual = max_q + b * (((q - max_q) / b).exp() + ((q_other - max_q) / b).exp()).ln();
+                    assert_close(actual, to_f64(target), 1e-9);
+                }
+            }
+        }
+        assert!(lmsr_quantity_for_cost(&[100, 100], 0, 50, 100 * SCALE).is_none());
+    }
+
+    #[test]
+    fn rescale_to_common_exponent() {
+        assert_eq!(rescale(20_050, -2, -8).unwrap(), 20_050_000_000);
+        assert_eq!(rescale(-7, 3, 0).unwrap(), -7_000);
+        assert_eq!(rescale(42, -5, -5).unwrap(), 42);
+        assert!(rescale(42, -5, -2).is_err());
+        assert!(rescale(i64::MAX as i128, 0, -40).is_err());
+    }
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(median(&[7]).unwrap(), 7);
+        assert_eq!(median(&[30, 10, 20]).unwrap(), 20);
+        assert_eq!(median(&[10, 21]).unwrap(), 15);
+        assert_eq!(median(&[-3, -4]).unwrap(), -4);
+        assert!(median(&[]).is_err());
+    }
+
+    #[test]
+    fn token_amount_rounding() {
+        assert_eq!(to_token_amount(SCALE + 1, 9, Rounding::Down).unwrap(), 1_000_000_000);
+        assert_eq!(to_token_amount(SCALE + 1, 9, Rounding::Up).unwrap(), 1_000_000_001);
+        assert!(to_token_amount(SCALE, 19, Rounding::Down).is_err());
+    }
+}
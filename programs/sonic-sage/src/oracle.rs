@@ -0,0 +1,416 @@
+use anchor_lang::prelude::*;
+use pyth_sdk_solana::{state::SolanaPriceAccount, PriceFeed};
+use pyth_solana_receiver_sdk::price_update::{PriceFeedMessage, PriceUpdateV2, VerificationLevel};
+use switchboard_on_demand::{PullFeedAccountData, PRECISION};
+
+use crate::errors::*;
+use crate::math::{median, rescale};
+use crate::state::config::BPS_DENOMINATOR;
+use crate::state::market::OracleKind;
+use crate::state::mock_oracle::MockOracle;
+
+/// Oracle price normalised across oracle providers, the price is `(price ± conf) * 10^exponent`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OraclePrice {
+    pub price: i128,
+    pub conf: u128,
+    pub exponent: i32,
+    /// Publish time of the price (unix timestamp in seconds)
+    pub publish_time: i64,
+}
+
+/// Reads the price of a price feed from the account of an oracle provider
+pub trait OracleAdapter {
+    /// Reads the price of the price feed from the oracle account
+    ///
+    /// # Arguments
+    ///
+    /// * `oracle` - Account of the oracle provider holding the price
+    /// * `feed_id` - ID of the price feed of the market
+    /// * `allow_partial_verification` - Whether partially verified prices are accepted
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the account is not a valid oracle account, holds the price of
+    /// another price feed or the price is not verified enough
+    fn read_price(
+        &self,
+        oracle: &AccountInfo,
+        feed_id: &[u8; 32],
+        allow_partial_verification: bool,
+    ) -> Result<OraclePrice>;
+
+    /// Whether the oracle provides an exponential moving average price
+    fn has_ema_price(&self) -> bool {
+        false
+    }
+
+    /// Reads the exponential moving average price of the price feed from the oracle account
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the oracle does not provide a moving average price, or as `read_price`
+    fn read_ema_price(&self, _oracle: &AccountInfo, _feed_id: &[u8; 32], _: bool) -> Result<OraclePrice> {
+        err!(CustomError::EmaPriceNotSupported)
+    }
+}
+
+/// Pyth pull oracle, a `PriceUpdateV2` account posted through the Pyth receiver program
+pub struct PythPullAdapter;
+
+/// Legacy Pyth push oracle, a price account of the Pyth oracle program
+/// The price feed ID is the address of the price account.
+pub struct PythPushAdapter;
+
+/// Switchboard On-Demand oracle, a pull feed account of the Switchboard program
+/// The price feed ID is the address of the pull feed account.
+pub struct SwitchboardAdapter;
+
+/// Mock oracle owned by this program, updated by the admin for testing on localnet
+/// Prices are only read in builds with the `localnet` feature.
+pub struct MockAdapter;
+
+/// Returns the adapter reading the prices of an oracle kind
+pub fn oracle_adapter(kind: OracleKind) -> &'static dyn OracleAdapter {
+    match kind {
+        OracleKind::PythPull => &PythPullAdapter,
+        OracleKind::PythPush => &PythPushAdapter,
+        OracleKind::SwitchboardOnDemand => &SwitchboardAdapter,
+        OracleKind::Mock => &MockAdapter,
+    }
+}
+
+/// Checks that an oracle kind is available in this build, the mock oracle being built only
+/// with the `localnet` feature
+///
+/// # Errors
+///
+/// Returns error if the oracle kind is the mock oracle and the program is not built for localnet
+pub fn check_oracle_kind(kind: OracleKind) -> Result<()> {
+    require!(
+        kind != OracleKind::Mock || cfg!(feature = "localnet"),
+        CustomError::MockOracleDisabled
+    );
+    Ok(())
+}
+
+/// Deserializes an oracle account of an Anchor program after checking its owner
+fn deserialize_oracle<T: AccountDeserialize + Owner>(oracle: &AccountInfo) -> Result<T> {
+    require_keys_eq!(*oracle.owner, T::owner(), CustomError::InvalidOracleAccount);
+    T::try_deserialize(&mut &oracle.try_borrow_data()?[..])
+}
+
+impl OracleAdapter for PythPullAdapter {
+    fn read_price(
+        &self,
+        oracle: &AccountInfo,
+        feed_id: &[u8; 32],
+        allow_partial_verification: bool,
+    ) -> Result<OraclePrice> {
+        let message = read_price_message(oracle, feed_id, allow_partial_verification)?;
+        Ok(OraclePrice {
+            price: message.price as i128,
+            conf: message.conf as u128,
+            exponent: message.exponent,
+            publish_time: message.publish_time,
+        })
+    }
+
+    fn has_ema_price(&self) -> bool {
+        true
+    }
+
+    fn read_ema_price(
+        &self,
+        oracle: &AccountInfo,
+        feed_id: &[u8; 32],
+        allow_partial_verification: bool,
+    ) -> Result<OraclePrice> {
+        let message = read_price_message(oracle, feed_id, allow_partial_verification)?;
+        Ok(OraclePrice {
+            price: message.ema_price as i128,
+            conf: message.ema_conf as u128,
+            exponent: message.exponent,
+            publish_time: message.publish_time,
+        })
+    }
+}
+
+/// Reads the price message of a Pyth price update after checking its verification level and price feed
+fn read_price_message(
+    oracle: &AccountInfo,
+    feed_id: &[u8; 32],
+    allow_partial_verification: bool,
+) -> Result<PriceFeedMessage> {
+    let price_update = deserialize_oracle::<PriceUpdateV2>(oracle)?;
+    let is_verified = match price_update.verification_level {
+        VerificationLevel::Full => true,
+        VerificationLevel::Partial { .. } => allow_partial_verification,
+    };
+    require!(is_verified, CustomError::InsufficientVerificationLevel);
+
+    let message = price_update.price_message;
+    require!(message.feed_id == *feed_id, CustomError::PriceFeedMismatch);
+    Ok(message)
+}
+
+impl OracleAdapter for PythPushAdapter {
+    fn read_price(&self, oracle: &AccountInfo, feed_id: &[u8; 32], _: bool) -> Result<OraclePrice> {
+        // The price of a feed that is not trading falls back to the last trading price and its publish time
+        let price = read_price_feed(oracle, feed_id)?.get_price_unchecked();
+        Ok(OraclePrice {
+            price: price.price as i128,
+            conf: price.conf as u128,
+            exponent: price.expo,
+            publish_time: price.publish_time,
+        })
+    }
+
+    fn has_ema_price(&self) -> bool {
+        true
+    }
+
+    fn read_ema_price(&self, oracle: &AccountInfo, feed_id: &[u8; 32], _: bool) -> Result<OraclePrice> {
+        let price = read_price_feed(oracle, feed_id)?.get_ema_price_unchecked();
+        Ok(OraclePrice {
+            price: price.price as i128,
+            conf: price.conf as u128,
+            exponent: price.expo,
+            publish_time: price.publish_time,
+        })
+    }
+}
+
+/// Reads the price feed of a legacy Pyth price account after checking it is the account of the price feed
+fn read_price_feed(oracle: &AccountInfo, feed_id: &[u8; 32]) -> Result<PriceFeed> {
+    require!(oracle.key.to_bytes() == *feed_id, CustomError::PriceFeedMismatch);
+    Ok(SolanaPriceAccount::account_info_to_feed(oracle).map_err(|_| CustomError::InvalidOracleAccount)?)
+}
+
+impl OracleAdapter for SwitchboardAdapter {
+    fn read_price(&self, oracle: &AccountInfo, feed_id: &[u8; 32], _: bool) -> Result<OraclePrice> {
+        require!(oracle.key.to_bytes() == *feed_id, CustomError::PriceFeedMismatch);
+
+        let data = oracle.try_borrow_data()?;
+        let feed = PullFeedAccountData::parse(data).map_err(|_| CustomError::InvalidOracleAccount)?;
+        // The result is the median of the oracle submissions, a zero slot means no result yet
+        require!(feed.result.slot != 0, CustomError::InvalidOracleAccount);
+        Ok(OraclePrice {
+            price: feed.result.value,
+            conf: feed.result.std_dev.unsigned_abs(),
+            exponent: -(PRECISION as i32),
+            publish_time: feed.last_update_timestamp,
+        })
+    }
+}
+
+impl OracleAdapter for MockAdapter {
+    fn read_price(&self, oracle: &AccountInfo, feed_id: &[u8; 32], _: bool) -> Result<OraclePrice> {
+        check_oracle_kind(OracleKind::Mock)?;
+        let mock_oracle = deserialize_oracle::<MockOracle>(oracle)?;
+        require!(mock_oracle.feed_id == *feed_id, CustomError::PriceFeedMismatch);
+        Ok(OraclePrice {
+            price: mock_oracle.price as i128,
+            conf: mock_oracle.conf as u128,
+            exponent: mock_oracle.exponent,
+            publish_time: mock_oracle.publish_time,
+        })
+    }
+}
+
+/// Combines the prices of several oracle sources into their median
+///
+/// The prices are compared exactly at the lowest of their exponents and `max_exponent`.
+/// The confidence interval of the median is the widest of the sources and its publish
+/// time the latest.
+///
+/// # Arguments
+///
+/// * `prices` - Prices of the oracle sources
+/// * `max_exponent` - Highest exponent of the result
+/// * `max_deviation_bps` - Maximum spread between the highest and lowest price, in basis points of the median
+///
+/// # Errors
+///
+/// Returns error if there are no prices or the spread between the highest and lowest price
+/// exceeds `max_deviation_bps` of the median
+pub fn median_price(prices: &[OraclePrice], max_exponent: i32, max_deviation_bps: u16) -> Result<OraclePrice> {
+    let exponent = prices.iter().map(|price| price.exponent).fold(max_exponent, i32::min);
+    let values = prices
+        .iter()
+        .map(|price| rescale(price.price, price.exponent, exponent))
+        .collect::<Result<Vec<i128>>>()?;
+    let median_value = median(&values)?;
+
+    let (lowest, highest) = (values.iter().min(), values.iter().max());
+    let spread = highest
+        .zip(lowest)
+        .and_then(|(highest, lowest)| highest.abs_diff(*lowest).checked_mul(BPS_DENOMINATOR as u128))
+        .ok_or(CustomError::MathOverflow)?;
+    let max_spread =
+        median_value.unsigned_abs().checked_mul(max_deviation_bps as u128).ok_or(CustomError::MathOverflow)?;
+    require!(spread <= max_spread, CustomError::OraclesDisagree);
+
+    let mut conf = 0;
+    for price in prices {
+        conf = conf.max(rescale(price.conf as i128, price.exponent, exponent)? as u128);
+    }
+    Ok(OraclePrice {
+        price: median_value,
+        conf,
+        exponent,
+        publish_time: prices.iter().map(|price| price.publish_time).max().unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_oracle_is_only_available_on_localnet() {
+        for kind in [OracleKind::PythPull, OracleKind::PythPush, OracleKind::SwitchboardOnDemand] {
+            assert!(check_oracle_kind(kind).is_ok());
+        }
+
+        let mock = check_oracle_kind(OracleKind::Mock);
+        if cfg!(feature = "localnet") {
+            assert!(mock.is_ok());
+        } else {
+            assert_eq!(mock.unwrap_err(), CustomError::MockOracleDisabled.into());
+        }
+    }
+
+    /// Serializes a Pyth price update of 200.50 ± 0.10 with a moving average of 200.40 ± 0.05
+    fn price_update_data(feed_id: [u8; 32], verification_level: VerificationLevel) -> Vec<u8> {
+        let price_update = PriceUpdateV2 {
+            write_authority: Pubkey::default(),
+            verification_level,
+            price_message: PriceFeedMessage {
+                feed_id,
+                price: 20_050,
+                conf: 10,
+                exponent: -2,
+                publish_time: 1_000,
+                prev_publish_time: 999,
+                ema_price: 20_040,
+                ema_conf: 5,
+            },
+            posted_slot: 1,
+        };
+        let mut data = Vec::new();
+        price_update.try_serialize(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn pyth_pull_oracle_reads_the_moving_average_price() {
+        let feed_id = [1; 32];
+        let key = Pubkey::new_unique();
+        let owner = PriceUpdateV2::owner();
+        let mut lamports = 0;
+        let mut data = price_update_data(feed_id, VerificationLevel::Full);
+        let oracle = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+
+        assert!(PythPullAdapter.has_ema_price());
+        assert_eq!(PythPullAdapter.read_price(&oracle, &feed_id, false).unwrap(), price(20_050, 10, -2, 1_000));
+        assert_eq!(PythPullAdapter.read_ema_price(&oracle, &feed_id, false).unwrap(), price(20_040, 5, -2, 1_000));
+        assert_eq!(
+            PythPullAdapter.read_ema_price(&oracle, &[2; 32], false).unwrap_err(),
+            CustomError::PriceFeedMismatch.into()
+        );
+
+        // The moving average of a partially verified update is only read if partial verification is allowed
+        let mut data = price_update_data(feed_id, VerificationLevel::Partial { num_signatures: 5 });
+        let oracle = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        assert_eq!(
+            PythPullAdapter.read_ema_price(&oracle, &feed_id, false).unwrap_err(),
+            CustomError::InsufficientVerificationLevel.into()
+        );
+        assert!(PythPullAdapter.read_ema_price(&oracle, &feed_id, true).is_ok());
+
+        // An account of another program is not a price update
+        let other_owner = Pubkey::new_unique();
+        let oracle = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &other_owner, false, 0);
+        assert!(PythPullAdapter.read_ema_price(&oracle, &feed_id, true).is_err());
+    }
+
+    #[test]
+    fn only_pyth_oracles_provide_a_moving_average_price() {
+        for kind in [OracleKind::PythPull, OracleKind::PythPush] {
+            assert!(oracle_adapter(kind).has_ema_price());
+        }
+
+        let feed_id = [1; 32];
+        let key = Pubkey::new_unique();
+        let owner = PriceUpdateV2::owner();
+        let mut lamports = 0;
+        let mut data = price_update_data(feed_id, VerificationLevel::Full);
+        let oracle = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        for kind in [OracleKind::SwitchboardOnDemand, OracleKind::Mock] {
+            let adapter = oracle_adapter(kind);
+            assert!(!adapter.has_ema_price());
+            assert_eq!(
+                adapter.read_ema_price(&oracle, &feed_id, true).unwrap_err(),
+                CustomError::EmaPriceNotSupported.into()
+            );
+        }
+    }
+
+    fn price(price: i128, conf: u128, exponent: i32, publish_time: i64) -> OraclePrice {
+        OraclePrice {
+            price,
+            conf,
+            exponent,
+            publish_time,
+        }
+    }
+
+    #[test]
+    fn median_price_compares_mixed_exponents_exactly() {
+        // 200.50 ± 0.01 from Pyth, 200.40 ± 0.02 from Switchboard and 200.60 ± 0.01 from the mock oracle
+        let prices = [
+            price(20_050_000_000, 1_000_000, -8, 1_000),
+            price(200_400_000_000_000_000_000, 20_000_000_000_000_000, -18, 1_002),
+            price(20_060, 1, -2, 1_001),
+        ];
+
+        let median = median_price(&prices, -2, 100).unwrap();
+        assert_eq!(median, price(200_500_000_000_000_000_000, 20_000_000_000_000_000, -18, 1_002));
+    }
+
+    #[test]
+    fn median_price_averages_two_sources() {
+        let prices = [price(20_061, 3, -2, 1_000), price(20_050, 2, -2, 1_000)];
+        assert_eq!(median_price(&prices, -2, 100).unwrap(), price(20_055, 3, -2, 1_000));
+
+        // The mean of the two middle values is rounded down
+        let prices = [price(-100, 1, 0, 1_000), price(-101, 1, 0, 1_000)];
+        assert_eq!(median_price(&prices, 0, 100).unwrap().price, -101);
+    }
+
+    #[test]
+    fn median_price_rescales_the_confidence_interval() {
+        // Rescaled to the market exponent when it is below the oracle exponent
+        let median = median_price(&[price(20_050, 5, -2, 1_000)], -4, 0).unwrap();
+        assert_eq!(median, price(2_005_000, 500, -4, 1_000));
+
+        // The confidence interval of the median is the widest of the sources at the common exponent
+        let prices = [price(20_050, 3, -2, 1_000), price(2_005_100, 200, -4, 1_000)];
+        assert_eq!(median_price(&prices, -2, 100).unwrap(), price(2_005_050, 300, -4, 1_000));
+    }
+
+    #[test]
+    fn median_price_rejects_oracles_that_disagree() {
+        // A spread of 100 within 100 bps of the median 10_050
+        let prices = [price(10_000, 1, -2, 1_000), price(10_100, 1, -2, 1_000)];
+        assert!(median_price(&prices, -2, 100).is_ok());
+        assert_eq!(median_price(&prices, -2, 99).unwrap_err(), CustomError::OraclesDisagree.into());
+
+        let prices = [price(20_000, 1, -2, 1_000), price(20_300, 1, -2, 1_000), price(20_150, 1, -2, 1_000)];
+        assert_eq!(median_price(&prices, -2, 100).unwrap_err(), CustomError::OraclesDisagree.into());
+        assert!(median_price(&prices, -2, 150).is_ok());
+
+        assert!(median_price(&[], -2, 100).is_err());
+    }
+}
@@ -0,0 +1,17 @@
+use anchor_lang::prelude::*;
+
+/// Account to store a collateral mint accepted by the program.
+/// Seeded by the mint, so its existence puts the mint on the allowlist.
+#[account]
+#[derive(InitSpace)]
+pub struct CollateralMint {
+    /// The accepted collateral mint.
+    pub mint: Pubkey,
+
+    /// Decimals of the mint, used to convert whole token amounts to base units.
+    pub decimals: u8,
+
+    /// Whether new markets can be created with this collateral.
+    /// Existing markets keep trading and redeeming when disabled.
+    pub is_enabled: bool,
+}
@@ -0,0 +1,42 @@
+use anchor_lang::prelude::*;
+
+use crate::state::market::OracleSource;
+
+/// Maximum fee that can be charged on a trade, in basis points
+pub const MAX_FEE_BPS: u16 = 1_000;
+
+/// Denominator of fees expressed in basis points
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Maximum age of an oracle price at resolution set on initialization, in seconds
+pub const DEFAULT_MAX_PRICE_AGE: u64 = 60;
+
+/// Maximum number of price feeds on the oracle allowlist
+pub const MAX_ALLOWED_PRICE_FEEDS: usize = 32;
+
+/// Account to store the global configuration of the program.
+#[account]
+#[derive(InitSpace)]
+pub struct Config {
+    /// Authority allowed to update the configuration and claim protocol fees.
+    pub admin: Pubkey,
+
+    /// Admin proposed by the current admin, who becomes admin once they accept.
+    pub pending_admin: Option<Pubkey>,
+
+    /// Fee charged by the protocol on every trade, in basis points.
+    pub protocol_fee_bps: u16,
+
+    /// Maximum fee a market creator can charge on every trade, in basis points.
+    pub max_creator_fee_bps: u16,
+
+    /// Upper bound on the maximum age of an oracle price that market creators can set, in seconds.
+    pub max_price_age: u64,
+
+    /// Flag to pause market creation, trading, resolution and redemption on all markets.
+    pub is_paused: bool,
+
+    /// Price feeds markets can be created on, each with the oracle provider it is read from.
+    #[max_len(MAX_ALLOWED_PRICE_FEEDS)]
+    pub allowed_price_feeds: Vec<OracleSource>,
+}
@@ -0,0 +1,341 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::*;
+use crate::instructions::buy_sell::get_prices;
+use crate::state::config::{BPS_DENOMINATOR, DEFAULT_MAX_PRICE_AGE};
+use crate::state::market::{
+    AmbiguousPriceAction, ConfidencePolicy, Market, MarketKind, OracleKind, PriceCondition, SettlementMode,
+    TwapAccumulator,
+};
+use crate::state::outcome::OutcomeAccount;
+
+/// LMSR liquidity parameter shared by all legacy markets
+pub const LEGACY_LIQUIDITY_B: u64 = 50;
+
+/// Exponent of the price threshold of a migrated legacy market
+pub const LEGACY_PRICE_EXPONENT: i32 = -8;
+
+/// Layout of a binary market created before per-market vaults, stored under the `Market` discriminator
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, InitSpace)]
+pub struct LegacyMarket {
+    /// Unique identifier for the market
+    pub id: u64,
+
+    /// Predicted price of the asset
+    pub price: f64,
+
+    /// The Pyth price feed ID, used to fetch the price of the speculated asset
+    #[max_len(66)]
+    pub price_feed_id: String,
+
+    /// The time when the market was created (in seconds)
+    pub created_at: u64,
+
+    /// The time when the market resolution starts (in milliseconds)
+    pub resolve_from: u64,
+
+    /// The time when the market resolution ends (in milliseconds)
+    pub resolve_to: u64,
+
+    /// The amount of subsidy that the market maker provided, in whole token units
+    pub subsidy_amount: u64,
+
+    /// The current balance of tokens in the market
+    pub current_balance: u64,
+
+    /// The number of shares of outcome 0 acc. to LMSR
+    pub num_outcome_0: u64,
+
+    /// The number of shares of outcome 1 acc. to LMSR
+    pub num_outcome_1: u64,
+
+    /// Total number of shares of outcome 0 held by users
+    pub num_outcome_0_held: u64,
+
+    /// Total number of shares of outcome 1 held by users
+    pub num_outcome_1_held: u64,
+
+    /// Current price of outcome 0 acc. to LMSR
+    pub price_outcome_0: f64,
+
+    /// Current price of outcome 1 acc. to LMSR
+    pub price_outcome_1: f64,
+
+    /// Flag to indicate if the market is resolved
+    pub is_resolved: bool,
+
+    /// The winning outcome of the market
+    pub outcome: Option<u8>,
+}
+
+/// Layout of a position in a legacy market, stored under the `OutcomeAccount` discriminator
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, InitSpace)]
+pub struct LegacyOutcomeAccount {
+    /// Balance of shares of outcome 0
+    pub amount_0: u64,
+
+    /// Balance of shares of outcome 1
+    pub amount_1: u64,
+}
+
+/// Deserializes an account with a legacy layout, checking its size and discriminator
+///
+/// # Arguments
+///
+/// * `data` - Data of the account
+/// * `discriminator` - Discriminator of the account type, unchanged by the migration
+/// * `space` - Space of the legacy layout, without the discriminator
+///
+/// # Errors
+///
+/// Returns error if the account does not have the legacy layout, in particular once it is migrated
+pub fn deserialize_legacy<T: AnchorDeserialize>(data: &[u8], discriminator: &[u8], space: usize) -> Result<T> {
+    require!(
+        data.len() == 8 + space && data.starts_with(discriminator),
+        CustomError::NotLegacyAccount
+    );
+    Ok(T::deserialize(&mut &data[8..])?)
+}
+
+impl LegacyMarket {
+    /// Converts the legacy market into a binary market on its Pyth price feed
+    ///
+    /// The predicted price becomes the single threshold, with exponent `LEGACY_PRICE_EXPONENT`,
+    /// so that outcome 0 still wins if the price is at or below it. The resolve window is
+    /// converted from milliseconds to seconds, and trading closes when it opens. The market keeps
+    /// the liquidity parameter, price age and confidence handling of legacy markets, and
+    /// charges no creator fee.
+    ///
+    /// Legacy markets did not track the cost basis of positions, so the balance beyond the
+    /// subsidy is reserved for refunds if the migrated market is voided, and shared between
+    /// the legacy positions by their number of shares as they are migrated.
+    ///
+    /// # Arguments
+    ///
+    /// * `creator` - Creator of the market, from the seeds of its address
+    /// * `collateral_mint` - Mint of the legacy shared token account
+    /// * `decimals` - Decimals of the collateral mint
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the predicted price cannot be represented as a threshold
+    /// or the subsidy overflows with decimals
+    pub fn into_market(self, creator: Pubkey, collateral_mint: Pubkey, decimals: u8) -> Result<Market> {
+        let subsidy_in_tokens = 10u64
+            .checked_pow(decimals as u32)
+            .and_then(|unit| self.subsidy_amount.checked_mul(unit))
+            .ok_or(CustomError::MathOverflow)?;
+        let legacy_cost_basis = self.current_balance.saturating_sub(subsidy_in_tokens);
+        let legacy_shares_held =
+            self.num_outcome_0_held.checked_add(self.num_outcome_1_held).ok_or(CustomError::MathOverflow)?;
+
+        let threshold = (self.price * 10f64.powi(-LEGACY_PRICE_EXPONENT)).round();
+        require!(
+            threshold.is_finite() && threshold.abs() < i64::MAX as f64,
+            CustomError::MathOverflow
+        );
+        let resolve_from = self.resolve_from / 1_000;
+
+        let mut market = Market {
+            id: self.id,
+            creator,
+            collateral_mint,
+            price_thresholds: vec![threshold as i64],
+            condition: PriceCondition::Thresholds,
+            outcome_labels: vec!["At or below".to_string(), "Above".to_string()],
+            price_exponent: LEGACY_PRICE_EXPONENT,
+            kind: MarketKind::Categorical,
+            oracle_kind: OracleKind::PythPull,
+            price_feed_id: self.price_feed_id,
+            additional_oracles: Vec::new(),
+            max_oracle_deviation_bps: 0,
+            oracle_readings: Vec::new(),
+            settlement_mode: SettlementMode::Spot,
+            twap: TwapAccumulator::default(),
+            created_at: self.created_at,
+            resolve_from,
+            resolve_to: self.resolve_to / 1_000,
+            trading_opens_at: self.created_at,
+            trading_closes_at: resolve_from,
+            max_price_age: DEFAULT_MAX_PRICE_AGE,
+            allow_partial_verification: false,
+            // Legacy markets resolve on any price whose confidence interval is within the price
+            confidence_policy: ConfidencePolicy {
+                conf_multiplier_bps: 0,
+                max_conf_bps: BPS_DENOMINATOR as u16,
+                on_ambiguous: AmbiguousPriceAction::Reject,
+            },
+            last_ambiguous_publish_time: 0,
+            subsidy_amount: self.subsidy_amount,
+            liquidity_b: LEGACY_LIQUIDITY_B,
+            current_balance: self.current_balance,
+            total_cost_basis: legacy_cost_basis,
+            legacy_cost_basis,
+            legacy_shares_held,
+            num_outcomes: 2,
+            quantities: vec![self.num_outcome_0, self.num_outcome_1],
+            shares_held: vec![self.num_outcome_0_held, self.num_outcome_1_held],
+            prices: Vec::new(),
+            creator_fee_bps: 0,
+            creator_fees_accrued: 0,
+            protocol_fees_accrued: 0,
+            is_paused: false,
+            is_resolved: self.is_resolved,
+            is_voided: false,
+            outcome: self.outcome,
+            scalar_payout: 0,
+        };
+        market.prices = get_prices(&market)?;
+
+        Ok(market)
+    }
+}
+
+impl LegacyOutcomeAccount {
+    /// Converts the legacy position into a position of its migrated binary market
+    ///
+    /// The position takes its share of the legacy cost basis of the market by its number of
+    /// shares, and the last legacy position to be migrated takes what is left of it.
+    ///
+    /// # Arguments
+    ///
+    /// * `market` - Migrated market of the position
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the position holds more shares than the legacy positions of the market
+    pub fn into_outcome_account(self, market: &mut Market) -> Result<OutcomeAccount> {
+        let shares = self.amount_0.checked_add(self.amount_1).ok_or(CustomError::MathOverflow)?;
+        require!(shares <= market.legacy_shares_held, CustomError::MathOverflow);
+
+        let cost_basis = if shares == market.legacy_shares_held {
+            market.legacy_cost_basis
+        } else {
+            (market.legacy_cost_basis as u128 * shares as u128 / market.legacy_shares_held as u128) as u64
+        };
+        market.legacy_cost_basis -= cost_basis;
+        market.legacy_shares_held -= shares;
+
+        Ok(OutcomeAccount {
+            amounts: vec![self.amount_0, self.amount_1],
+            cost_basis,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instructions::redeem::settle_position;
+    use crate::instructions::refund::{reclaimable_subsidy, refund_amount};
+    use anchor_lang::Discriminator;
+
+    fn legacy_market() -> LegacyMarket {
+        LegacyMarket {
+            id: 7,
+            price: 200.5,
+            price_feed_id: "0xe62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43".to_string(),
+            created_at: 1_700_000_000,
+            resolve_from: 1_700_086_400_000,
+            resolve_to: 1_700_172_800_000,
+            subsidy_amount: 100,
+            current_balance: 105_000_000_000,
+            num_outcome_0: 110,
+            num_outcome_1: 100,
+            num_outcome_0_held: 10,
+            num_outcome_1_held: 0,
+            price_outcome_0: 0.55,
+            price_outcome_1: 0.45,
+            is_resolved: false,
+            outcome: None,
+        }
+    }
+
+    #[test]
+    fn deserializes_only_the_legacy_layout() {
+        let mut data = Market::DISCRIMINATOR.to_vec();
+        legacy_market().serialize(&mut data).unwrap();
+        data.resize(8 + LegacyMarket::INIT_SPACE, 0);
+
+        let legacy = deserialize_legacy::<LegacyMarket>(&data, &Market::DISCRIMINATOR, LegacyMarket::INIT_SPACE);
+        assert_eq!(legacy.unwrap(), legacy_market());
+
+        let wrong_discriminator = deserialize_legacy::<LegacyMarket>(
+            &data,
+            &OutcomeAccount::DISCRIMINATOR,
+            LegacyMarket::INIT_SPACE,
+        );
+        assert_eq!(wrong_discriminator.unwrap_err(), CustomError::NotLegacyAccount.into());
+
+        // A migrated market is larger than the legacy layout
+        data.resize(8 + Market::INIT_SPACE, 0);
+        let migrated = deserialize_legacy::<LegacyMarket>(&data, &Market::DISCRIMINATOR, LegacyMarket::INIT_SPACE);
+        assert_eq!(migrated.unwrap_err(), CustomError::NotLegacyAccount.into());
+    }
+
+    #[test]
+    fn converts_a_legacy_market() {
+        let creator = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let market = legacy_market().into_market(creator, mint, 9).unwrap();
+
+        assert_eq!(market.creator, creator);
+        assert_eq!(market.collateral_mint, mint);
+        assert_eq!(market.price_thresholds, vec![20_050_000_000]);
+        assert_eq!(market.price_exponent, LEGACY_PRICE_EXPONENT);
+        assert_eq!(market.resolve_from, 1_700_086_400);
+        assert_eq!(market.resolve_to, 1_700_172_800);
+        assert_eq!(market.trading_closes_at, market.resolve_from);
+        assert_eq!(market.quantities, vec![110, 100]);
+        assert_eq!(market.shares_held, vec![10, 0]);
+        assert_eq!(market.liquidity_b, LEGACY_LIQUIDITY_B);
+        assert_eq!(market.current_balance, 105_000_000_000);
+        assert!(market.prices[0] > market.prices[1]);
+        assert_eq!(market.prices.iter().map(|price| *price as u128).sum::<u128>(), crate::math::SCALE);
+
+        let mut unrepresentable = legacy_market();
+        unrepresentable.price = f64::NAN;
+        assert!(unrepresentable.into_market(creator, mint, 9).is_err());
+    }
+
+    #[test]
+    fn refunds_migrated_positions_of_a_voided_market() {
+        let mut legacy = legacy_market();
+        legacy.num_outcome_1_held = 5;
+        let mut market = legacy.into_market(Pubkey::new_unique(), Pubkey::new_unique(), 9).unwrap();
+
+        // The balance beyond the subsidy is the cost basis of the legacy positions
+        assert_eq!(market.total_cost_basis, 5_000_000_000);
+        assert_eq!(market.legacy_shares_held, 15);
+
+        let positions = [
+            LegacyOutcomeAccount { amount_0: 4, amount_1: 3 },
+            LegacyOutcomeAccount { amount_0: 6, amount_1: 0 },
+            LegacyOutcomeAccount { amount_0: 0, amount_1: 2 },
+        ];
+        let mut migrated: Vec<OutcomeAccount> =
+            positions.into_iter().map(|position| position.into_outcome_account(&mut market).unwrap()).collect();
+        assert_eq!(
+            migrated.iter().map(|position| position.cost_basis).collect::<Vec<_>>(),
+            vec![2_333_333_333, 2_000_000_000, 666_666_667]
+        );
+        assert_eq!((market.legacy_cost_basis, market.legacy_shares_held), (0, 0));
+
+        let extra = LegacyOutcomeAccount { amount_0: 1, amount_1: 0 };
+        let res = extra.into_outcome_account(&mut market).map(|_| ());
+        assert_eq!(res.unwrap_err(), CustomError::MathOverflow.into());
+
+        market.is_resolved = true;
+        market.is_voided = true;
+        for position in migrated.iter_mut() {
+            let refund = refund_amount(&market, position.cost_basis);
+            assert_eq!(refund, position.cost_basis);
+            market.current_balance -= refund;
+            settle_position(&mut market, position).unwrap();
+        }
+
+        // Only the subsidy is left for the creator
+        assert_eq!(market.total_cost_basis, 0);
+        assert_eq!(reclaimable_subsidy(&market), 100_000_000_000);
+    }
+}
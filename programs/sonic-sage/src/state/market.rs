@@ -1,57 +1,501 @@
 use anchor_lang::prelude::*;
 
+use crate::errors::*;
+use crate::math::rescale;
+
+/// Maximum number of outcomes of a categorical market
+pub const MAX_OUTCOMES: usize = 16;
+
+/// Maximum length of an outcome label, in bytes
+pub const MAX_LABEL_LEN: usize = 32;
+
+/// Outcome index of the SHORT shares of a scalar market
+pub const SHORT_OUTCOME: u8 = 0;
+
+/// Outcome index of the LONG shares of a scalar market
+pub const LONG_OUTCOME: u8 = 1;
+
+/// Maximum number of oracle sources of a market, the primary oracle included
+pub const MAX_ORACLE_SOURCES: usize = 3;
+
+/// Minimum number of observations of the oracle price to settle a market on their time-weighted average
+pub const MIN_TWAP_OBSERVATIONS: u32 = 3;
+
+/// Lowest exponent of the price thresholds and bounds of a market, down to which they are rescaled at resolution
+pub const MIN_PRICE_EXPONENT: i32 = -18;
+
+/// Highest exponent of the price thresholds and bounds of a market
+pub const MAX_PRICE_EXPONENT: i32 = 18;
+
+/// Kind of a market, deciding how it is resolved and redeemed
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, InitSpace)]
+pub enum MarketKind {
+    /// Exactly one outcome wins, selected by the price thresholds
+    Categorical,
+
+    /// LONG shares pay `(clamp(price) - lower) / (upper - lower)` per share
+    /// and SHORT shares the complement
+    /// The bounds have the exponent `price_exponent` of the market.
+    Scalar { lower: i64, upper: i64 },
+}
+
+/// Oracle provider the price feed of a market is read from at resolution
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum OracleKind {
+    /// Pyth pull oracle, the price feed ID is the Pyth feed ID
+    PythPull,
+
+    /// Legacy Pyth push oracle, the price feed ID is the address of the price account
+    PythPush,
+
+    /// Switchboard On-Demand oracle, the price feed ID is the address of the pull feed account
+    SwitchboardOnDemand,
+
+    /// Mock oracle of this program for testing on localnet, updated by the admin
+    Mock,
+}
+
+impl OracleKind {
+    /// Whether both oracle kinds are run by the same provider, Pyth pull and push prices
+    /// being aggregated from the same publishers
+    pub fn is_same_provider(self, other: OracleKind) -> bool {
+        let is_pyth = |kind: OracleKind| matches!(kind, OracleKind::PythPull | OracleKind::PythPush);
+        self == other || (is_pyth(self) && is_pyth(other))
+    }
+}
+
+/// Price feed of an oracle provider a market is resolved from, in addition to its primary oracle
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct OracleSource {
+    /// Oracle provider of the price feed
+    pub kind: OracleKind,
+
+    /// ID of the price feed, the account address for push and Switchboard feeds
+    pub feed_id: [u8; 32],
+}
+
+/// Price read from an oracle source at resolution, recorded on the market for auditing
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct OracleReading {
+    /// Oracle provider the price was read from
+    pub kind: OracleKind,
+
+    /// The price, standing for `price * 10^exponent`
+    pub price: i128,
+
+    /// The confidence interval of the price, with the same exponent
+    pub conf: u128,
+
+    /// The exponent of the price and confidence interval
+    pub exponent: i32,
+
+    /// The publish time of the price (unix timestamp in seconds)
+    pub publish_time: i64,
+}
+
+/// Condition on the price deciding the winning outcome of a categorical market
+/// The binary conditions compare the price to the price thresholds: outcome 0 wins
+/// if the condition holds and outcome 1 otherwise.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum PriceCondition {
+    /// Outcome `i` wins if exactly `i` of the ascending thresholds are below the price
+    Thresholds,
+
+    /// The price is above the single threshold
+    GreaterThan,
+
+    /// The price is at or above the single threshold
+    GreaterOrEqual,
+
+    /// The price is below the single threshold
+    LessThan,
+
+    /// The price is at or below the single threshold
+    LessOrEqual,
+
+    /// The price is inside the bracket `[low, high)` of the two thresholds
+    Inside,
+}
+
+/// Price a market is settled on at resolution
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum SettlementMode {
+    /// Spot price of the oracles
+    Spot,
+
+    /// Exponential moving average price of the oracles, only available for Pyth price feeds
+    Ema,
+
+    /// Time-weighted average of the spot prices observed with `record_observation`
+    /// during the resolve window, settled once the window has closed and voided if the average is ambiguous
+    Twap,
+}
+
+/// Time-weighted average of the oracle prices observed during the resolve window
+/// Every observed price is held until the next observation, the last one until `resolve_to`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct TwapAccumulator {
+    /// Number of observations recorded
+    pub observations: u32,
+
+    /// Publish time of the first observed price (unix timestamp in seconds)
+    pub first_publish_time: i64,
+
+    /// Publish time of the last observed price (unix timestamp in seconds)
+    pub last_publish_time: i64,
+
+    /// Last observed price
+    pub last_price: i128,
+
+    /// Sum of the observed prices, each weighted by the seconds until the next observation
+    pub cumulative_price: i128,
+
+    /// Exponent of the observed prices, set by the first observation
+    pub exponent: i32,
+
+    /// Widest confidence interval of the observed prices
+    pub max_conf: u128,
+}
+
+impl TwapAccumulator {
+    /// Records an observed price, which must be published after the last observation
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the price is not published after the last observation, has a lower
+    /// exponent than the first observation or the cumulative price overflows
+    pub fn record(&mut self, price: i128, conf: u128, exponent: i32, publish_time: i64) -> Result<()> {
+        if self.observations == 0 {
+            self.first_publish_time = publish_time;
+            self.exponent = exponent;
+        } else {
+            require!(publish_time > self.last_publish_time, CustomError::ObservationNotAfterLast);
+            let held_for = (publish_time - self.last_publish_time) as i128;
+            self.cumulative_price = self
+                .last_price
+                .checked_mul(held_for)
+                .and_then(|weighted| self.cumulative_price.checked_add(weighted))
+                .ok_or(CustomError::MathOverflow)?;
+        }
+
+        self.last_price = rescale(price, exponent, self.exponent)?;
+        self.max_conf = self.max_conf.max(rescale(conf as i128, exponent, self.exponent)? as u128);
+        self.last_publish_time = publish_time;
+        self.observations = self.observations.checked_add(1).ok_or(CustomError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Calculates the time-weighted average price from the first observation until `end_time`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the cumulative price overflows
+    ///
+    /// # Returns
+    ///
+    /// The average price, rounded down, with the exponent of the observations
+    pub fn average(&self, end_time: i64) -> Result<i128> {
+        let duration = end_time.saturating_sub(self.first_publish_time) as i128;
+        if duration == 0 {
+            return Ok(self.last_price);
+        }
+
+        let held_for = end_time.saturating_sub(self.last_publish_time) as i128;
+        let cumulative_price = self
+            .last_price
+            .checked_mul(held_for)
+            .and_then(|weighted| self.cumulative_price.checked_add(weighted))
+            .ok_or(CustomError::MathOverflow)?;
+        Ok(cumulative_price.div_euclid(duration))
+    }
+}
+
+/// Action taken at resolution when the oracle price is too close to a price threshold to tell the outcome
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum AmbiguousPriceAction {
+    /// The price update is rejected and the market can be resolved with another price
+    Reject,
+
+    /// The market stays unresolved and can only be resolved with a price published later
+    RetryLater,
+
+    /// The market is voided
+    Void,
+}
+
+/// How the confidence interval of the oracle price is taken into account at resolution
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct ConfidencePolicy {
+    /// Multiple `k` of the confidence interval, in basis points, within which a price
+    /// is ambiguous: `|price - threshold| < k * conf`
+    pub conf_multiplier_bps: u32,
+
+    /// Maximum ratio of the confidence interval to the price, in basis points,
+    /// above which the price update is rejected
+    pub max_conf_bps: u16,
+
+    /// Action taken when the price is ambiguous
+    pub on_ambiguous: AmbiguousPriceAction,
+}
+
 /// Represents a prediction market where users can trade outcomes of future events
-/// The Market struct stores all the essential information about a binary prediction market.
+/// The Market struct stores all the essential information about a categorical prediction market,
+/// binary markets being the special case of two outcomes.
 #[account]
 #[derive(InitSpace)]
 pub struct Market {
     /// Unique identifier for the market
     pub id: u64,
 
-    /// Predicted price of the asset
-    pub price: f64,
+    /// The account that created the market and receives the creator fees
+    pub creator: Pubkey,
+
+    /// The mint of the collateral used to trade and redeem outcome shares
+    pub collateral_mint: Pubkey,
+
+    /// Ascending price thresholds splitting the asset price into one range per outcome
+    /// Outcome `i` wins if the actual price is above `i` of the thresholds, so a binary
+    /// market has a single threshold, the predicted price of the asset.
+    /// A comparison condition has a single threshold and a bracket the two bounds `[low, high)`.
+    #[max_len(MAX_OUTCOMES - 1)]
+    pub price_thresholds: Vec<i64>,
+
+    /// Condition on the price deciding the winning outcome of a categorical market
+    pub condition: PriceCondition,
+
+    /// Human-readable label of every outcome
+    #[max_len(MAX_OUTCOMES, MAX_LABEL_LEN)]
+    pub outcome_labels: Vec<String>,
 
-    /// The Pyth price feed ID, used to fetch the price of the speculated asset
+    /// Exponent of the price thresholds and scalar bounds, a value `v` stands for the price `v * 10^price_exponent`
+    pub price_exponent: i32,
+
+    /// Kind of the market, categorical or scalar
+    pub kind: MarketKind,
+
+    /// Oracle provider of the price feed
+    pub oracle_kind: OracleKind,
+
+    /// The hex encoded price feed ID, used to fetch the price of the speculated asset
     #[max_len(66)]
     pub price_feed_id: String,
 
-    /// The time when the market was created (in seconds)
+    /// Oracle sources of other providers the market is resolved from along with the primary oracle
+    /// The market resolves on the median of all the sources.
+    #[max_len(MAX_ORACLE_SOURCES - 1)]
+    pub additional_oracles: Vec<OracleSource>,
+
+    /// Maximum spread between the highest and lowest price of the oracle sources,
+    /// in basis points of the median, above which the market cannot be resolved
+    pub max_oracle_deviation_bps: u16,
+
+    /// Prices of the oracle sources read at the last resolution attempt or observation, the primary oracle first
+    #[max_len(MAX_ORACLE_SOURCES)]
+    pub oracle_readings: Vec<OracleReading>,
+
+    /// Price the market is settled on at resolution
+    pub settlement_mode: SettlementMode,
+
+    /// Time-weighted average of the prices observed for a market settled on `SettlementMode::Twap`
+    pub twap: TwapAccumulator,
+
+    /// The time when the market was created (unix timestamp in seconds)
     pub created_at: u64,
 
-    /// The time when the market resolution starts (in milliseconds)
+    /// The time when the market resolution starts (unix timestamp in seconds)
     pub resolve_from: u64,
 
-    /// The time when the market resolution ends (in milliseconds)
+    /// The time when the market resolution ends (unix timestamp in seconds)
+    /// The oracle price used for resolution must be published within `[resolve_from, resolve_to]`.
     pub resolve_to: u64,
 
+    /// The time when trading opens (unix timestamp in seconds)
+    pub trading_opens_at: u64,
+
+    /// The time when trading closes (unix timestamp in seconds)
+    /// No later than `resolve_from`, so shares cannot be traded once the outcome is known.
+    pub trading_closes_at: u64,
+
+    /// The maximum age of the oracle price at resolution, in seconds
+    pub max_price_age: u64,
+
+    /// Flag to accept partially verified oracle price updates at resolution
+    pub allow_partial_verification: bool,
+
+    /// The policy applied to the confidence interval of the oracle price at resolution
+    pub confidence_policy: ConfidencePolicy,
+
+    /// Publish time of the last ambiguous price retried under `AmbiguousPriceAction::RetryLater`
+    /// (unix timestamp in seconds), the market can only be resolved with a price published after it
+    pub last_ambiguous_publish_time: u64,
+
     /// The amount of subsidy that the market maker provided
     /// In whole token units, not with decimals.
     pub subsidy_amount: u64,
 
+    /// The LMSR liquidity parameter `b` of the market
+    /// Higher values create less price movement per trade.
+    pub liquidity_b: u64,
+
     /// The current balance of tokens in the market
     pub current_balance: u64,
 
-    /// The number of shares of outcome 0 acc. to LMSR
-    pub num_outcome_0: u64,
+    /// Sum of the cost basis of all outcome accounts of the market (with decimals),
+    /// reserved for refunds if the market is voided
+    pub total_cost_basis: u64,
+
+    /// Cost basis of the positions not yet migrated from a legacy market (with decimals),
+    /// included in `total_cost_basis` and shared between them by their number of shares
+    pub legacy_cost_basis: u64,
 
-    /// The number of shares of outcome 1 acc. to LMSR
-    pub num_outcome_1: u64,
+    /// Total number of shares of the positions not yet migrated from a legacy market
+    pub legacy_shares_held: u64,
 
-    /// Total number of shares of outcome 0 held by users
-    pub num_outcome_0_held: u64,
+    /// The number of outcomes of the market
+    pub num_outcomes: u8,
 
-    /// Total number of shares of outcome 1 held by users
-    pub num_outcome_1_held: u64,
+    /// The number of shares of every outcome acc. to LMSR
+    #[max_len(MAX_OUTCOMES)]
+    pub quantities: Vec<u64>,
 
-    /// Current price of outcome 0 acc. to LMSR
-    pub price_outcome_0: f64,
+    /// Total number of shares of every outcome held by users
+    #[max_len(MAX_OUTCOMES)]
+    pub shares_held: Vec<u64>,
 
-    /// Current price of outcome 1 acc. to LMSR
-    pub price_outcome_1: f64,
+    /// Current price of every outcome acc. to LMSR, scaled by `math::SCALE`
+    #[max_len(MAX_OUTCOMES)]
+    pub prices: Vec<u64>,
+
+    /// Fee charged by the market creator on every trade, in basis points
+    pub creator_fee_bps: u16,
+
+    /// Total creator fees accrued by the market (with decimals)
+    pub creator_fees_accrued: u64,
+
+    /// Total protocol fees accrued by the market (with decimals)
+    pub protocol_fees_accrued: u64,
+
+    /// Flag set by the admin to pause trading, resolution and redemption on the market
+    pub is_paused: bool,
 
     /// Flag to indicate if the market is resolved
     pub is_resolved: bool,
 
-    /// The winning outcome of the market
+    /// Flag to indicate if the market was voided instead of resolved,
+    /// in which case traders are refunded their cost basis
+    pub is_voided: bool,
+
+    /// The winning outcome of a categorical market
     pub outcome: Option<u8>,
-}
\ No newline at end of file
+
+    /// Payout of a LONG share of a resolved scalar market, scaled by `math::SCALE`
+    /// A SHORT share pays the complement `SCALE - scalar_payout`.
+    pub scalar_payout: u64,
+}
+
+/// Binary market on the price threshold 200.50 resolving within `[1_000, 2_000]`, for unit tests
+#[cfg(test)]
+pub fn test_market() -> Market {
+    Market {
+        id: 0,
+        creator: Pubkey::default(),
+        collateral_mint: Pubkey::default(),
+        price_thresholds: vec![20_050],
+        condition: PriceCondition::Thresholds,
+        outcome_labels: vec!["At or below".to_string(), "Above".to_string()],
+        price_exponent: -2,
+        kind: MarketKind::Categorical,
+        oracle_kind: OracleKind::PythPull,
+        price_feed_id: String::new(),
+        additional_oracles: Vec::new(),
+        max_oracle_deviation_bps: 0,
+        oracle_readings: Vec::new(),
+        settlement_mode: SettlementMode::Spot,
+        twap: TwapAccumulator::default(),
+        created_at: 0,
+        resolve_from: 1_000,
+        resolve_to: 2_000,
+        trading_opens_at: 0,
+        trading_closes_at: 1_000,
+        max_price_age: 60,
+        allow_partial_verification: false,
+        confidence_policy: ConfidencePolicy {
+            conf_multiplier_bps: 20_000,
+            max_conf_bps: 100,
+            on_ambiguous: AmbiguousPriceAction::Reject,
+        },
+        last_ambiguous_publish_time: 0,
+        subsidy_amount: 100,
+        liquidity_b: 50,
+        current_balance: 0,
+        total_cost_basis: 0,
+        legacy_cost_basis: 0,
+        legacy_shares_held: 0,
+        num_outcomes: 2,
+        quantities: vec![100, 100],
+        shares_held: vec![0, 0],
+        prices: Vec::new(),
+        creator_fee_bps: 0,
+        creator_fees_accrued: 0,
+        protocol_fees_accrued: 0,
+        is_paused: false,
+        is_resolved: false,
+        is_voided: false,
+        outcome: None,
+        scalar_payout: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn twap_weights_each_price_until_the_next_observation() {
+        let mut twap = TwapAccumulator::default();
+        twap.record(20_000, 10, -2, 1_000).unwrap();
+        // A single observation is held until the end of the window
+        assert_eq!(twap.average(1_000).unwrap(), 20_000);
+        assert_eq!(twap.average(1_100).unwrap(), 20_000);
+
+        // Prices of a higher exponent are rescaled to the exponent of the first observation
+        twap.record(201, 1, 0, 1_010).unwrap();
+        twap.record(20_050, 20, -2, 1_030).unwrap();
+        assert_eq!(twap.observations, 3);
+        assert_eq!(twap.exponent, -2);
+        assert_eq!(twap.max_conf, 100);
+        assert_eq!(twap.first_publish_time, 1_000);
+        assert_eq!(twap.last_publish_time, 1_030);
+
+        // (20_000 * 10 + 20_100 * 20 + 20_050 * 10) / 40 = 20_062.5, rounded down
+        assert_eq!(twap.average(1_040).unwrap(), 20_062);
+        // The last price is held until the end of the window
+        assert_eq!(twap.average(1_230).unwrap(), (200_000 + 402_000 + 20_050 * 200) / 230);
+    }
+
+    #[test]
+    fn twap_observations_must_be_ordered_and_representable() {
+        let mut twap = TwapAccumulator::default();
+        twap.record(20_000, 10, -2, 1_000).unwrap();
+        twap.record(20_100, 10, -2, 1_010).unwrap();
+
+        // Records on a copy, as a failed observation reverts the transaction
+        let record_err = |price, exponent, publish_time| {
+            let mut copy = twap;
+            copy.record(price, 10, exponent, publish_time).unwrap_err()
+        };
+        for publish_time in [1_010, 1_005] {
+            assert_eq!(record_err(20_050, -2, publish_time), CustomError::ObservationNotAfterLast.into());
+        }
+        // A lower exponent than the first observation cannot be rescaled exactly
+        assert_eq!(record_err(2_005_000, -4, 1_020), CustomError::MathOverflow.into());
+
+        // The cumulative price overflows once an extreme price is held
+        let mut overflowing = twap;
+        overflowing.record(i128::MAX, 10, -2, 1_020).unwrap();
+        assert_eq!(overflowing.average(1_030).unwrap_err(), CustomError::MathOverflow.into());
+        assert_eq!(overflowing.record(20_050, 10, -2, 1_030).unwrap_err(), CustomError::MathOverflow.into());
+
+        assert!(twap.record(20_050, 10, -2, 1_020).is_ok());
+    }
+}
@@ -0,0 +1,22 @@
+use anchor_lang::prelude::*;
+
+/// Account to store the price of a mock price feed, for testing markets on localnet.
+/// Seeded by the price feed ID and updated by the admin.
+#[account]
+#[derive(InitSpace)]
+pub struct MockOracle {
+    /// The ID of the mocked price feed.
+    pub feed_id: [u8; 32],
+
+    /// The price, standing for `price * 10^exponent`.
+    pub price: i64,
+
+    /// The confidence interval of the price, with the same exponent.
+    pub conf: u64,
+
+    /// The exponent of the price and confidence interval.
+    pub exponent: i32,
+
+    /// The publish time of the price (unix timestamp in seconds).
+    pub publish_time: i64,
+}
@@ -1,3 +1,7 @@
 pub mod market;
 pub mod outcome;
-pub mod metadata;
\ No newline at end of file
+pub mod metadata;
+pub mod config;
+pub mod collateral;
+pub mod mock_oracle;
+pub mod legacy;
\ No newline at end of file
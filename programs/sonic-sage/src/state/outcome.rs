@@ -1,12 +1,16 @@
 use anchor_lang::prelude::*;
 
+use crate::state::market::MAX_OUTCOMES;
+
 /// Account to store the outcome shares of a user.
 #[account]
 #[derive(InitSpace)]
 pub struct OutcomeAccount {
-    /// Balance of shares of outcome 0.
-    pub amount_0: u64,
+    /// Balance of shares of every outcome.
+    #[max_len(MAX_OUTCOMES)]
+    pub amounts: Vec<u64>,
 
-    /// Balance of shares of outcome 1.
-    pub amount_1: u64,
+    /// Net collateral paid into the market for these shares, excluding fees (with decimals).
+    /// Refunded if the market is voided.
+    pub cost_basis: u64,
 }
\ No newline at end of file
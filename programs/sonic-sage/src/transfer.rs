@@ -0,0 +1,98 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    spl_token_2022::{
+        extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions},
+        state::Mint as MintState,
+    },
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+use crate::errors::*;
+
+/// Reads the transfer fee configuration of a Token-2022 mint, if it has the transfer fee extension
+fn transfer_fee_config(mint: &InterfaceAccount<Mint>) -> Result<Option<TransferFeeConfig>> {
+    let mint_info = mint.to_account_info();
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<MintState>::unpack(&mint_data)?;
+    Ok(mint_state.get_extension::<TransferFeeConfig>().ok().copied())
+}
+
+/// Calculates the amount to transfer so that the recipient receives `net_amount`
+/// after the transfer fee of a Token-2022 mint
+///
+/// Mints without the transfer fee extension, including legacy SPL Token mints,
+/// charge no fee, so the net amount is returned unchanged.
+///
+/// # Arguments
+///
+/// * `mint` - Mint of the tokens to transfer
+/// * `net_amount` - Amount the recipient must receive (with decimals)
+///
+/// # Returns
+///
+/// The amount to transfer (with decimals), including the transfer fee
+pub fn gross_amount(mint: &InterfaceAccount<Mint>, net_amount: u64) -> Result<u64> {
+    let Some(transfer_fee_config) = transfer_fee_config(mint)? else {
+        return Ok(net_amount);
+    };
+
+    let fee = transfer_fee_config
+        .calculate_inverse_epoch_fee(Clock::get()?.epoch, net_amount)
+        .ok_or(CustomError::MathOverflow)?;
+    Ok(net_amount.checked_add(fee).ok_or(CustomError::MathOverflow)?)
+}
+
+/// Calculates the amount the recipient receives when `amount` is transferred,
+/// after the transfer fee of a Token-2022 mint
+///
+/// # Arguments
+///
+/// * `mint` - Mint of the tokens to transfer
+/// * `amount` - Amount to transfer (with decimals), including the transfer fee
+///
+/// # Returns
+///
+/// The amount the recipient receives (with decimals)
+pub fn received_amount(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let Some(transfer_fee_config) = transfer_fee_config(mint)? else {
+        return Ok(amount);
+    };
+
+    let fee = transfer_fee_config
+        .calculate_epoch_fee(Clock::get()?.epoch, amount)
+        .ok_or(CustomError::MathOverflow)?;
+    Ok(amount.checked_sub(fee).ok_or(CustomError::MathOverflow)?)
+}
+
+/// Transfers tokens with `transfer_checked`, which supports both SPL Token and Token-2022 mints
+///
+/// # Arguments
+///
+/// * `token_program` - Token program owning the mint
+/// * `from` - Token account to transfer from
+/// * `to` - Token account to transfer to
+/// * `authority` - Owner of the `from` token account
+/// * `mint` - Mint of the tokens to transfer
+/// * `amount` - Amount to transfer (with decimals), before any transfer fee
+/// * `signer_seeds` - Seeds of the authority if it is a PDA, empty otherwise
+pub fn transfer_tokens<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    from: &InterfaceAccount<'info, TokenAccount>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    authority: &AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let cpi_accounts = TransferChecked {
+        from: from.to_account_info(),
+        mint: mint.to_account_info(),
+        to: to.to_account_info(),
+        authority: authority.clone(),
+    };
+    transfer_checked(
+        CpiContext::new(token_program.to_account_info(), cpi_accounts).with_signer(signer_seeds),
+        amount,
+        mint.decimals,
+    )
+}
@@ -0,0 +1,186 @@
+import { expect } from "chai";
+import { AnchorProvider, BN, setProvider, workspace } from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { createMint, TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { Keypair, PublicKey, SystemProgram } from "@solana/web3.js";
+import { SonicSage } from "../target/types/sonic_sage";
+import {
+  getCollateralMintPda,
+  ORACLE_KIND,
+  PRICE_FEED_ID,
+  setupProgram,
+} from "./helpers";
+
+describe("admin", () => {
+  const provider = AnchorProvider.local();
+  setProvider(provider);
+  const pg = workspace.SonicSage as Program<SonicSage>;
+  const signer = provider.wallet.payer;
+  let configPda: PublicKey;
+
+  before(async () => {
+    const x = await setupProgram({ pg, signer });
+    configPda = x.configPda;
+  });
+
+  it("allows the test price feed", async () => {
+    const config = await pg.account.config.fetch(configPda);
+
+    expect(config.allowedPriceFeeds.length).to.equal(1);
+    expect(config.allowedPriceFeeds[0].kind).to.deep.equal(ORACLE_KIND);
+    expect(
+      Buffer.from(config.allowedPriceFeeds[0].feedId).toString("hex")
+    ).to.equal(PRICE_FEED_ID.slice(2));
+  });
+
+  it("allows a price feed only for its oracle provider", async () => {
+    const switchboardKind = { switchboardOnDemand: {} };
+    await pg.methods
+      .addAllowedPriceFeed(switchboardKind, PRICE_FEED_ID)
+      .accounts({ config: configPda, admin: signer.publicKey })
+      .signers([signer])
+      .rpc();
+
+    let config = await pg.account.config.fetch(configPda);
+    expect(config.allowedPriceFeeds.length).to.equal(2);
+    expect(config.allowedPriceFeeds[1].kind).to.deep.equal(switchboardKind);
+
+    // The same feed id of another provider is a different allowlist entry
+    await pg.methods
+      .removeAllowedPriceFeed(switchboardKind, PRICE_FEED_ID)
+      .accounts({ config: configPda, admin: signer.publicKey })
+      .signers([signer])
+      .rpc();
+
+    config = await pg.account.config.fetch(configPda);
+    expect(config.allowedPriceFeeds.length).to.equal(1);
+    expect(config.allowedPriceFeeds[0].kind).to.deep.equal(ORACLE_KIND);
+  });
+
+  it("pauses and unpauses the program", async () => {
+    await pg.methods
+      .setPaused(true)
+      .accounts({ config: configPda, admin: signer.publicKey })
+      .signers([signer])
+      .rpc();
+    expect((await pg.account.config.fetch(configPda)).isPaused).to.equal(true);
+
+    await pg.methods
+      .setPaused(false)
+      .accounts({ config: configPda, admin: signer.publicKey })
+      .signers([signer])
+      .rpc();
+    expect((await pg.account.config.fetch(configPda)).isPaused).to.equal(false);
+  });
+
+  it("defaults and updates the maximum price age", async () => {
+    let config = await pg.account.config.fetch(configPda);
+    expect(config.maxPriceAge.toNumber()).to.equal(60);
+
+    await pg.methods
+      .setMaxPriceAge(new BN(120))
+      .accounts({ config: configPda, admin: signer.publicKey })
+      .signers([signer])
+      .rpc();
+    config = await pg.account.config.fetch(configPda);
+    expect(config.maxPriceAge.toNumber()).to.equal(120);
+
+    let error;
+    await pg.methods
+      .setMaxPriceAge(new BN(0))
+      .accounts({ config: configPda, admin: signer.publicKey })
+      .signers([signer])
+      .rpc()
+      .catch((err) => {
+        error = err;
+      });
+    expect(error.error.errorCode.code).to.equal("InvalidMaxPriceAge");
+  });
+
+  it("sets the price of a mock price feed", async () => {
+    const feedId = Array.from(Buffer.from(PRICE_FEED_ID.slice(2), "hex"));
+    const [mockOraclePda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("mock_oracle"), Buffer.from(feedId)],
+      pg.programId
+    );
+
+    await pg.methods
+      .setMockPrice(feedId, new BN(2_010_000), new BN(500), -2, new BN(1_700_000_000))
+      .accounts({ config: configPda, mockOracle: mockOraclePda, admin: signer.publicKey })
+      .signers([signer])
+      .rpc();
+
+    const mockOracle = await pg.account.mockOracle.fetch(mockOraclePda);
+    expect(mockOracle.feedId).to.deep.equal(feedId);
+    expect(mockOracle.price.toNumber()).to.equal(2_010_000);
+    expect(mockOracle.conf.toNumber()).to.equal(500);
+    expect(mockOracle.exponent).to.equal(-2);
+  });
+
+  it("rejects a collateral mint with more decimals than the fixed-point math", async () => {
+    const mint = await createMint(
+      pg.provider.connection,
+      signer,
+      signer.publicKey,
+      null,
+      19
+    );
+    const [protocolFeeVaultPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("protocol_fees"), mint.toBuffer()],
+      pg.programId
+    );
+
+    let error;
+    await pg.methods
+      .addCollateralMint()
+      .accounts({
+        config: configPda,
+        collateralMint: getCollateralMintPda(pg, mint),
+        protocolFeeVault: protocolFeeVaultPda,
+        mint,
+        admin: signer.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([signer])
+      .rpc()
+      .catch((err) => {
+        error = err;
+      });
+    expect(error.error.errorCode.code).to.equal("InvalidCollateralDecimals");
+  });
+
+  it("transfers the admin authority in two steps", async () => {
+    const newAdmin = Keypair.generate();
+
+    await pg.methods
+      .proposeAdmin(newAdmin.publicKey)
+      .accounts({ config: configPda, admin: signer.publicKey })
+      .signers([signer])
+      .rpc();
+    let config = await pg.account.config.fetch(configPda);
+    expect(config.admin.equals(signer.publicKey)).to.be.true;
+    expect(config.pendingAdmin.equals(newAdmin.publicKey)).to.be.true;
+
+    await pg.methods
+      .acceptAdmin()
+      .accounts({ config: configPda, pendingAdmin: newAdmin.publicKey })
+      .signers([newAdmin])
+      .rpc();
+    config = await pg.account.config.fetch(configPda);
+    expect(config.admin.equals(newAdmin.publicKey)).to.be.true;
+    expect(config.pendingAdmin).to.equal(null);
+
+    // Hand the admin authority back for the other tests
+    await pg.methods
+      .proposeAdmin(signer.publicKey)
+      .accounts({ config: configPda, admin: newAdmin.publicKey })
+      .signers([newAdmin])
+      .rpc();
+    await pg.methods
+      .acceptAdmin()
+      .accounts({ config: configPda, pendingAdmin: signer.publicKey })
+      .signers([signer])
+      .rpc();
+  });
+});
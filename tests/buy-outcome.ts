@@ -11,11 +11,29 @@ import { Program } from "@coral-xyz/anchor";
 import { Account, getAccount, getMint } from "@solana/spl-token";
 import { PublicKey } from "@solana/web3.js";
 import { SonicSage } from "../target/types/sonic_sage";
-import { setupProgram } from "./helpers";
+import {
+  CONFIDENCE_POLICY,
+  MAX_PRICE_AGE,
+  ORACLE_KIND,
+  OUTCOME_LABELS,
+  PRICE_CONDITION,
+  SETTLEMENT_MODE,
+  CREATOR_FEE_BPS,
+  getCollateralMintPda,
+  getCpiEvents,
+  getCreatorFeeVaultPda,
+  getMarketVaultPda,
+  setupProgram,
+  nowInSeconds,
+  PRICE_EXPONENT,
+} from "./helpers";
 
-const ONE_DAY = 1000 * 60 * 60 * 24;
+// Market timestamps are unix timestamps in seconds
+const ONE_DAY = 60 * 60 * 24;
+// Outcome prices are fixed-point values with 18 decimals
+const HALF = new BN("500000000000000000");
 
-describe.skip("buy outcome", () => {
+describe("buy outcome", () => {
   const provider = AnchorProvider.local();
   setProvider(provider);
   const connection = provider.connection;
@@ -26,19 +44,19 @@ describe.skip("buy outcome", () => {
     [Buffer.from("metadata")],
     programId
   );
-  const [programTokenAccountPda] = web3.PublicKey.findProgramAddressSync(
-    [Buffer.from("token")],
-    programId
-  );
   let metadata;
   let marketPda: PublicKey;
   let mint: PublicKey;
   let signerTokenAccount: Account;
+  let configPda: PublicKey;
+  let protocolFeeVaultPda: PublicKey;
 
   before(async () => {
     const x = await setupProgram({ pg, signer });
     mint = x.mint;
     signerTokenAccount = x.signerTokenAccount;
+    configPda = x.configPda;
+    protocolFeeVaultPda = x.protocolFeeVaultPda;
 
     metadata = await pg.account.metadata.fetch(metadataPda);
     [marketPda] = web3.PublicKey.findProgramAddressSync(
@@ -52,19 +70,23 @@ describe.skip("buy outcome", () => {
   });
 
   it("buy outcome", async () => {
-    const price = 200.5;
-    const resolveFrom = new BN(Date.now() + ONE_DAY);
-    const resolveTo = new BN(Date.now() + ONE_DAY * 2);
+    const priceThresholds = [new BN(20050)];
+    const resolveFrom = new BN(nowInSeconds() + ONE_DAY);
+    const resolveTo = new BN(nowInSeconds() + ONE_DAY * 2);
     const subsidyAmount = new BN(100);
+    const liquidityB = new BN(50);
     const priceFeedId =
       "0xe62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43";
 
     const createMarketAccounts = {
       market: marketPda,
       metadata: metadataPda,
+      config: configPda,
       mint,
+      collateralMint: getCollateralMintPda(pg, mint),
       signerTokenAccount: signerTokenAccount.address,
-      programTokenAccount: programTokenAccountPda,
+      marketVault: getMarketVaultPda(pg, marketPda),
+      creatorFeeVault: getCreatorFeeVaultPda(pg, marketPda),
       signer: signer.publicKey,
       tokenProgram: utils.token.TOKEN_PROGRAM_ID,
       associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
@@ -72,7 +94,27 @@ describe.skip("buy outcome", () => {
     };
 
     await pg.methods
-      .createMarket(price, priceFeedId, resolveFrom, resolveTo, subsidyAmount)
+      .createMarket(
+        priceThresholds,
+        PRICE_EXPONENT,
+        PRICE_CONDITION,
+        OUTCOME_LABELS,
+        ORACLE_KIND,
+        priceFeedId,
+        resolveFrom,
+        resolveTo,
+        subsidyAmount,
+        liquidityB,
+        CREATOR_FEE_BPS,
+        null,
+        null,
+        CONFIDENCE_POLICY,
+        MAX_PRICE_AGE,
+        false,
+        [],
+        0,
+        SETTLEMENT_MODE
+      )
       .accounts(createMarketAccounts)
       .signers([signer])
       .rpc();
@@ -82,18 +124,19 @@ describe.skip("buy outcome", () => {
     let market = await pg.account.market.fetch(marketPda);
     const mintAccount = await getMint(connection, mint);
     const mintAccountDecimals = mintAccount.decimals;
-    let programTokenAccount = await getAccount(
+    let marketVault = await getAccount(
       connection,
-      programTokenAccountPda
+      getMarketVaultPda(pg, marketPda)
     );
     console.log("mint", mintAccount.address.toBase58(), mintAccount.decimals);
     console.log(
-      "programTokenAccount",
-      programTokenAccount.amount.toString(),
-      programTokenAccount.mint.toBase58()
+      "marketVault",
+      marketVault.amount.toString(),
+      marketVault.mint.toBase58()
     );
 
     const numBuyOutcome0 = subsidyAmount.div(new BN(10));
+    const maxCost = subsidyAmount.mul(new BN(10 ** mintAccountDecimals));
     const [outcomeAccountPda] = await web3.PublicKey.findProgramAddressSync(
       [
         Buffer.from("outcome"),
@@ -106,36 +149,211 @@ describe.skip("buy outcome", () => {
       market: marketPda,
       subsidyMint: mint,
       signerTokenAccount: signerTokenAccount.address,
-      programTokenAccount: programTokenAccountPda,
+      marketVault: getMarketVaultPda(pg, marketPda),
       outcomeAccount: outcomeAccountPda,
+      config: configPda,
+      protocolFeeVault: protocolFeeVaultPda,
+      creatorFeeVault: getCreatorFeeVaultPda(pg, marketPda),
       signer: signer.publicKey,
       tokenProgram: utils.token.TOKEN_PROGRAM_ID,
       associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
       systemProgram: web3.SystemProgram.programId,
     };
     const txHash = await pg.methods
-      .buyOutcome(0, numBuyOutcome0)
+      .buyOutcome(0, numBuyOutcome0, maxCost, null)
       .accounts(buyOutcomeAccounts)
       .signers([signer])
-      .rpc()
-      .catch((err) => {
-        console.log(err);
-      });
-    console.log("txHash:", txHash);
+      .rpc({ commitment: "confirmed" });
 
     const outcomeAccount = await pg.account.outcomeAccount.fetch(
       outcomeAccountPda
     );
     market = await pg.account.market.fetch(marketPda);
-    programTokenAccount = await getAccount(connection, programTokenAccountPda);
+    marketVault = await getAccount(
+      connection,
+      getMarketVaultPda(pg, marketPda)
+    );
 
     expect(market.isResolved).to.equal(false);
     expect(market.outcome).to.equal(null);
-    expect(market.numOutcome0.toString()).to.equal(
+    expect(market.quantities[0].toString()).to.equal(
       subsidyAmount.add(numBuyOutcome0).toString()
     );
-    expect(market.numOutcome1.toString()).to.equal(subsidyAmount.toString());
-    expect(market.priceOutcome0).to.be.greaterThan(0.5);
-    expect(market.priceOutcome1).to.be.lessThan(0.5);
+    expect(market.quantities[1].toString()).to.equal(subsidyAmount.toString());
+    expect(market.prices[0].gt(HALF)).to.be.true;
+    expect(market.prices[1].lt(HALF)).to.be.true;
+
+    const creatorFeeVault = await getAccount(
+      connection,
+      getCreatorFeeVaultPda(pg, marketPda)
+    );
+    expect(market.creatorFeesAccrued.toString()).to.equal(
+      creatorFeeVault.amount.toString()
+    );
+    expect(market.creatorFeesAccrued.gt(new BN(0))).to.be.true;
+    expect(market.protocolFeesAccrued.gt(new BN(0))).to.be.true;
+
+    // The trade is emitted as an event with the post-trade prices
+    const [tradeEvent] = (await getCpiEvents(pg, txHash)).filter(
+      (event) => event.name === "trade"
+    );
+    expect(tradeEvent.data.market.equals(marketPda)).to.be.true;
+    expect(tradeEvent.data.side).to.deep.equal({ buy: {} });
+    expect(tradeEvent.data.outcome).to.equal(0);
+    expect(tradeEvent.data.shares.toString()).to.equal(
+      numBuyOutcome0.toString()
+    );
+    expect(tradeEvent.data.prices[0].toString()).to.equal(
+      market.prices[0].toString()
+    );
+  });
+
+  it("rejects buy above max cost", async () => {
+    const [outcomeAccountPda] = web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("outcome"),
+        marketPda.toBuffer(),
+        signer.publicKey.toBuffer(),
+      ],
+      programId
+    );
+    const buyOutcomeAccounts = {
+      market: marketPda,
+      subsidyMint: mint,
+      signerTokenAccount: signerTokenAccount.address,
+      marketVault: getMarketVaultPda(pg, marketPda),
+      outcomeAccount: outcomeAccountPda,
+      config: configPda,
+      protocolFeeVault: protocolFeeVaultPda,
+      creatorFeeVault: getCreatorFeeVaultPda(pg, marketPda),
+      signer: signer.publicKey,
+      tokenProgram: utils.token.TOKEN_PROGRAM_ID,
+      associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
+      systemProgram: web3.SystemProgram.programId,
+    };
+    const marketBefore = await pg.account.market.fetch(marketPda);
+
+    let error;
+    await pg.methods
+      .buyOutcome(0, new BN(10), new BN(1), null)
+      .accounts(buyOutcomeAccounts)
+      .signers([signer])
+      .rpc()
+      .catch((err) => {
+        error = err;
+      });
+
+    const market = await pg.account.market.fetch(marketPda);
+    expect(error.error.errorCode.code).to.equal("SlippageExceeded");
+    expect(market.quantities[0].toString()).to.equal(
+      marketBefore.quantities[0].toString()
+    );
+  });
+
+  it("rejects sell of more shares than held", async () => {
+    const [outcomeAccountPda] = web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("outcome"),
+        marketPda.toBuffer(),
+        signer.publicKey.toBuffer(),
+      ],
+      programId
+    );
+    const sellOutcomeAccounts = {
+      market: marketPda,
+      subsidyMint: mint,
+      signerTokenAccount: signerTokenAccount.address,
+      marketVault: getMarketVaultPda(pg, marketPda),
+      outcomeAccount: outcomeAccountPda,
+      config: configPda,
+      protocolFeeVault: protocolFeeVaultPda,
+      creatorFeeVault: getCreatorFeeVaultPda(pg, marketPda),
+      signer: signer.publicKey,
+      tokenProgram: utils.token.TOKEN_PROGRAM_ID,
+      associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
+      systemProgram: web3.SystemProgram.programId,
+    };
+    const outcomeAccount = await pg.account.outcomeAccount.fetch(
+      outcomeAccountPda
+    );
+
+    let error;
+    await pg.methods
+      .sellOutcome(0, outcomeAccount.amounts[0].addn(1), new BN(0), null)
+      .accounts(sellOutcomeAccounts)
+      .signers([signer])
+      .rpc()
+      .catch((err) => {
+        error = err;
+      });
+
+    expect(error.error.errorCode.code).to.equal("InsufficientShares");
+    // The position keeps its shares
+    const outcomeAccountAfter = await pg.account.outcomeAccount.fetch(
+      outcomeAccountPda
+    );
+    expect(outcomeAccountAfter.amounts[0].toString()).to.equal(
+      outcomeAccount.amounts[0].toString()
+    );
+  });
+
+  it("buy outcome with exact collateral amount", async () => {
+    const [outcomeAccountPda] = web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("outcome"),
+        marketPda.toBuffer(),
+        signer.publicKey.toBuffer(),
+      ],
+      programId
+    );
+    const buyOutcomeAccounts = {
+      market: marketPda,
+      subsidyMint: mint,
+      signerTokenAccount: signerTokenAccount.address,
+      marketVault: getMarketVaultPda(pg, marketPda),
+      outcomeAccount: outcomeAccountPda,
+      config: configPda,
+      protocolFeeVault: protocolFeeVaultPda,
+      creatorFeeVault: getCreatorFeeVaultPda(pg, marketPda),
+      signer: signer.publicKey,
+      tokenProgram: utils.token.TOKEN_PROGRAM_ID,
+      associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
+      systemProgram: web3.SystemProgram.programId,
+    };
+    const mintAccount = await getMint(connection, mint);
+    const collateralAmount = new BN(25).mul(
+      new BN(10 ** mintAccount.decimals)
+    );
+    const marketBefore = await pg.account.market.fetch(marketPda);
+    const balanceBefore = (await getAccount(connection, signerTokenAccount.address))
+      .amount;
+
+    await pg.methods
+      .buyOutcomeExactIn(1, collateralAmount, new BN(1), null)
+      .accounts(buyOutcomeAccounts)
+      .signers([signer])
+      .rpc();
+
+    const market = await pg.account.market.fetch(marketPda);
+    const balanceAfter = (await getAccount(connection, signerTokenAccount.address))
+      .amount;
+    const spent = new BN((balanceBefore - balanceAfter).toString());
+
+    expect(market.quantities[1].gt(marketBefore.quantities[1])).to.be.true;
+    expect(spent.lte(collateralAmount)).to.be.true;
+    expect(spent.gt(new BN(0))).to.be.true;
+
+    // The same collateral cannot buy more shares than the market sells for it
+    const sharesBought = market.quantities[1].sub(marketBefore.quantities[1]);
+    let error;
+    await pg.methods
+      .buyOutcomeExactIn(1, collateralAmount, sharesBought.muln(2), null)
+      .accounts(buyOutcomeAccounts)
+      .signers([signer])
+      .rpc()
+      .catch((err) => {
+        error = err;
+      });
+    expect(error.error.errorCode.code).to.equal("SlippageExceeded");
   });
 });
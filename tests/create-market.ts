@@ -11,9 +11,26 @@ import { Program } from "@coral-xyz/anchor";
 import { Account, getAccount, getMint } from "@solana/spl-token";
 import { PublicKey } from "@solana/web3.js";
 import { SonicSage } from "../target/types/sonic_sage";
-import { setupProgram } from "./helpers";
+import {
+  CONFIDENCE_POLICY,
+  MAX_PRICE_AGE,
+  ORACLE_KIND,
+  OUTCOME_LABELS,
+  PRICE_CONDITION,
+  SETTLEMENT_MODE,
+  CREATOR_FEE_BPS,
+  getCollateralMintPda,
+  getCreatorFeeVaultPda,
+  getMarketVaultPda,
+  setupProgram,
+  nowInSeconds,
+  PRICE_EXPONENT,
+} from "./helpers";
 
-const ONE_DAY = 1000 * 60 * 60 * 24;
+// Market timestamps are unix timestamps in seconds
+const ONE_DAY = 60 * 60 * 24;
+// Outcome prices are fixed-point values with 18 decimals
+const HALF = new BN("500000000000000000");
 
 describe.skip("create market", () => {
   const provider = AnchorProvider.local();
@@ -26,14 +43,11 @@ describe.skip("create market", () => {
     [Buffer.from("metadata")],
     programId
   );
-  const [programTokenAccountPda] = web3.PublicKey.findProgramAddressSync(
-    [Buffer.from("token")],
-    programId
-  );
   let metadata;
   let marketPda: PublicKey;
   let mint: PublicKey;
   let signerTokenAccount: Account;
+  let configPda: PublicKey;
 
   before(async () => {
     // console.log('pubkey:', signer.publicKey.toBase58());
@@ -42,6 +56,7 @@ describe.skip("create market", () => {
     const x = await setupProgram({ pg, signer });
     mint = x.mint;
     signerTokenAccount = x.signerTokenAccount;
+    configPda = x.configPda;
 
     metadata = await pg.account.metadata.fetch(metadataPda);
     [marketPda] = web3.PublicKey.findProgramAddressSync(
@@ -55,19 +70,23 @@ describe.skip("create market", () => {
   });
 
   it("create market", async () => {
-    const price = 200.5;
-    const resolveFrom = new BN(Date.now() + ONE_DAY);
-    const resolveTo = new BN(Date.now() + ONE_DAY * 2);
+    const priceThresholds = [new BN(20050)];
+    const resolveFrom = new BN(nowInSeconds() + ONE_DAY);
+    const resolveTo = new BN(nowInSeconds() + ONE_DAY * 2);
     const subsidyAmount = new BN(100);
+    const liquidityB = new BN(50);
     const priceFeedId =
       "0xe62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43";
 
     const accounts = {
       market: marketPda,
       metadata: metadataPda,
+      config: configPda,
       mint,
+      collateralMint: getCollateralMintPda(pg, mint),
       signerTokenAccount: signerTokenAccount.address,
-      programTokenAccount: programTokenAccountPda,
+      marketVault: getMarketVaultPda(pg, marketPda),
+      creatorFeeVault: getCreatorFeeVaultPda(pg, marketPda),
       signer: signer.publicKey,
       tokenProgram: utils.token.TOKEN_PROGRAM_ID,
       associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
@@ -79,7 +98,27 @@ describe.skip("create market", () => {
     // })
 
     const txHash = await pg.methods
-      .createMarket(price, priceFeedId, resolveFrom, resolveTo, subsidyAmount)
+      .createMarket(
+        priceThresholds,
+        PRICE_EXPONENT,
+        PRICE_CONDITION,
+        OUTCOME_LABELS,
+        ORACLE_KIND,
+        priceFeedId,
+        resolveFrom,
+        resolveTo,
+        subsidyAmount,
+        liquidityB,
+        CREATOR_FEE_BPS,
+        null,
+        null,
+        CONFIDENCE_POLICY,
+        MAX_PRICE_AGE,
+        false,
+        [],
+        0,
+        SETTLEMENT_MODE
+      )
       .accounts(accounts)
       .signers([signer])
       .rpc()
@@ -94,20 +133,20 @@ describe.skip("create market", () => {
     const market = await pg.account.market.fetch(marketPda);
     const mintAccount = await getMint(connection, mint);
     const mintAccountDecimals = mintAccount.decimals;
-    const programTokenAccount = await getAccount(
+    const marketVault = await getAccount(
       connection,
-      programTokenAccountPda
+      getMarketVaultPda(pg, marketPda)
     );
     console.log("mint", mintAccount.address.toBase58(), mintAccount.decimals);
     console.log(
-      "programTokenAccount",
-      programTokenAccount.amount.toString(),
-      programTokenAccount.mint.toBase58()
+      "marketVault",
+      marketVault.amount.toString(),
+      marketVault.mint.toBase58()
     );
 
-    const totalSubsidyProvided = programTokenAccount.amount.toString();
+    const totalSubsidyProvided = marketVault.amount.toString();
 
-    console.log("market.price:", market.price.toString());
+    console.log("market.priceThresholds:", market.priceThresholds);
     console.log("market.priceFeedId:", market.priceFeedId);
     console.log("market.resolveFrom:", market.resolveFrom.toString());
     console.log("market.resolveTo:", market.resolveTo.toString());
@@ -116,23 +155,48 @@ describe.skip("create market", () => {
     console.log("market.isResolved:", market.isResolved);
     console.log("market.outcome:", market.outcome);
     console.log("market.subsidyAmount:", market.subsidyAmount.toString());
-    console.log("market.numOutcome0:", market.numOutcome0.toString());
-    console.log("market.numOutcome1:", market.numOutcome1.toString());
-    console.log("market.priceOutcome0:", market.priceOutcome0.toString());
-    console.log("market.priceOutcome1:", market.priceOutcome1.toString());
+    console.log("market.quantities:", market.quantities.map((q) => q.toString()));
+    console.log("market.prices:", market.prices.map((p) => p.toString()));
     console.log("totalSubsidyProvided:", totalSubsidyProvided);
 
     expect(metadata.marketCounter.toString()).to.equal("1");
-    expect(market.price.toString()).to.equal(price.toString());
+    expect(market.priceThresholds.map((t) => t.toString())).to.deep.equal(
+      priceThresholds.map((t) => t.toString())
+    );
+    expect(market.priceExponent).to.equal(PRICE_EXPONENT);
+    expect(market.kind).to.deep.equal({ categorical: {} });
+    expect(market.numOutcomes).to.equal(2);
+    expect(market.condition).to.deep.equal(PRICE_CONDITION);
+    expect(market.outcomeLabels).to.deep.equal(OUTCOME_LABELS);
+    expect(market.oracleKind).to.deep.equal(ORACLE_KIND);
+    expect(market.additionalOracles).to.deep.equal([]);
+    expect(market.oracleReadings).to.deep.equal([]);
+    expect(market.settlementMode).to.deep.equal(SETTLEMENT_MODE);
+    expect(market.twap.observations).to.equal(0);
     expect(market.priceFeedId).to.equal(priceFeedId);
     expect(market.resolveFrom.toString()).to.equal(resolveFrom.toString());
     expect(market.resolveTo.toString()).to.equal(resolveTo.toString());
     expect(market.subsidyAmount.toString()).to.equal(subsidyAmount.toString());
+    expect(market.liquidityB.toString()).to.equal(liquidityB.toString());
+    expect(market.creator.equals(signer.publicKey)).to.be.true;
+    expect(market.collateralMint.equals(mint)).to.be.true;
+    expect(market.creatorFeeBps).to.equal(CREATOR_FEE_BPS);
+    expect(market.confidencePolicy.maxConfBps).to.equal(
+      CONFIDENCE_POLICY.maxConfBps
+    );
+    expect(market.confidencePolicy.onAmbiguous).to.deep.equal({ reject: {} });
+    expect(market.maxPriceAge.toString()).to.equal(MAX_PRICE_AGE.toString());
+    expect(market.allowPartialVerification).to.equal(false);
+    // Trading closes when the resolve window opens
+    expect(market.tradingClosesAt.toString()).to.equal(resolveFrom.toString());
+    expect(market.tradingOpensAt.toString()).to.equal(
+      market.createdAt.toString()
+    );
     expect(market.currentBalance.toString()).to.equal(subsidyAmount.toString());
-    expect(market.numOutcome0.toString()).to.equal(subsidyAmount.toString());
-    expect(market.numOutcome1.toString()).to.equal(subsidyAmount.toString());
-    expect(market.priceOutcome0.toString()).to.equal("0.5");
-    expect(market.priceOutcome1.toString()).to.equal("0.5");
+    expect(market.quantities[0].toString()).to.equal(subsidyAmount.toString());
+    expect(market.quantities[1].toString()).to.equal(subsidyAmount.toString());
+    expect(market.prices[0].toString()).to.equal(HALF.toString());
+    expect(market.prices[1].toString()).to.equal(HALF.toString());
     expect(market.isResolved).to.equal(false);
     expect(market.outcome).to.equal(null);
     expect(totalSubsidyProvided).to.equal(
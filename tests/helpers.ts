@@ -1,8 +1,6 @@
-import { Program, web3 } from "@coral-xyz/anchor";
+import { BN, Program, utils, web3 } from "@coral-xyz/anchor";
 import {
-  Account,
   createMint,
-  getAccount,
   getOrCreateAssociatedTokenAccount,
   mintTo,
   TOKEN_PROGRAM_ID,
@@ -15,6 +13,39 @@ import {
 } from "@solana/web3.js";
 import { SonicSage } from "../target/types/sonic_sage";
 
+export const PROTOCOL_FEE_BPS = 50;
+export const CREATOR_FEE_BPS = 100;
+// Prices within 2 confidence intervals of a threshold are rejected,
+// as are prices whose confidence interval exceeds 1% of the price
+export const CONFIDENCE_POLICY = {
+  confMultiplierBps: 20_000,
+  maxConfBps: 100,
+  onAmbiguous: { reject: {} },
+};
+
+// Outcome 0 wins if the price is at or below the single threshold of the test markets
+export const PRICE_CONDITION = { thresholds: {} };
+export const OUTCOME_LABELS = ["At or below", "Above"];
+
+// Oracle provider of the test price feed
+export const ORACLE_KIND = { pythPull: {} };
+
+// Price the test markets are settled on
+export const SETTLEMENT_MODE = { spot: {} };
+
+// Maximum age of the oracle price at resolution, in seconds
+export const MAX_PRICE_AGE = new BN(60);
+
+// Pyth price feed the test markets are created on
+export const PRICE_FEED_ID =
+  "0xe62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43";
+
+// Exponent of the test price thresholds, a threshold of 20050 stands for 200.50
+export const PRICE_EXPONENT = -2;
+
+// Current unix timestamp in seconds, the unit of all market timestamps
+export const nowInSeconds = () => Math.floor(Date.now() / 1000);
+
 export const createToken = async (args: {
   connection: Connection;
   owner: Keypair;
@@ -60,11 +91,6 @@ export const setupProgram = async (args: {
     [Buffer.from("metadata")],
     programId
   );
-  const [programTokenAccountPda] = web3.PublicKey.findProgramAddressSync(
-    [Buffer.from("token")],
-    programId
-  );
-
   const mint: PublicKey = await createToken({
     connection,
     owner: signer,
@@ -85,13 +111,39 @@ export const setupProgram = async (args: {
     amount: 10000 * LAMPORTS_PER_SOL,
   });
 
+  const [configPda] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("config")],
+    programId
+  );
+  const [protocolFeeVaultPda] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("protocol_fees"), mint.toBuffer()],
+    programId
+  );
+  // Only the upgrade authority of the program can initialize the configuration
+  const [programDataPda] = web3.PublicKey.findProgramAddressSync(
+    [programId.toBuffer()],
+    new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  );
+  await pg.methods
+    .initializeConfig(PROTOCOL_FEE_BPS)
+    .accounts({
+      config: configPda,
+      program: programId,
+      programData: programDataPda,
+      signer: signer.publicKey,
+      systemProgram: web3.SystemProgram.programId,
+    })
+    .signers([signer])
+    .rpc()
+    .catch((err) => {
+      console.log(err);
+    });
+
   console.log("Setting up...");
   const accounts = {
     metadata: metadataPda,
-    tokenAccount: programTokenAccountPda,
-    mint,
-    signer: signer.publicKey,
-    tokenProgram: TOKEN_PROGRAM_ID,
+    config: configPda,
+    admin: signer.publicKey,
     systemProgram: web3.SystemProgram.programId,
   };
   Object.keys(accounts).map((k) => {
@@ -108,22 +160,78 @@ export const setupProgram = async (args: {
     });
   console.log(`setup:txHash: ${txHash}`);
 
-  const programTokenAccount: Account = await getAccount(
-    connection,
-    programTokenAccountPda
-  );
+  // Allow markets to be created on the test price feed
+  await pg.methods
+    .addAllowedPriceFeed(ORACLE_KIND, PRICE_FEED_ID)
+    .accounts({ config: configPda, admin: signer.publicKey })
+    .signers([signer])
+    .rpc()
+    .catch((err) => {
+      console.log(err);
+    });
+
+  // Allow the mint as collateral, creating its protocol fee vault
+  await pg.methods
+    .addCollateralMint()
+    .accounts({
+      config: configPda,
+      collateralMint: getCollateralMintPda(pg, mint),
+      protocolFeeVault: protocolFeeVaultPda,
+      mint,
+      admin: signer.publicKey,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      systemProgram: web3.SystemProgram.programId,
+    })
+    .signers([signer])
+    .rpc()
+    .catch((err) => {
+      console.log(err);
+    });
 
   return {
     mint,
     signerTokenAccount,
-    programTokenAccount,
     metadataPda,
-    programTokenAccountPda,
+    configPda,
+    protocolFeeVaultPda,
   };
 };
 
+export const getCollateralMintPda = (
+  pg: Program<SonicSage>,
+  mint: PublicKey
+) => {
+  const [collateralMintPda] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("collateral"), mint.toBuffer()],
+    pg.programId
+  );
+  return collateralMintPda;
+};
+
+export const getMarketVaultPda = (
+  pg: Program<SonicSage>,
+  market: PublicKey
+) => {
+  const [marketVaultPda] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("vault"), market.toBuffer()],
+    pg.programId
+  );
+  return marketVaultPda;
+};
+
+export const getCreatorFeeVaultPda = (
+  pg: Program<SonicSage>,
+  market: PublicKey
+) => {
+  const [creatorFeeVaultPda] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("creator_fees"), market.toBuffer()],
+    pg.programId
+  );
+  return creatorFeeVaultPda;
+};
+
 export const logMarket = (market) => {
-  console.log("market.price:", market.price.toString());
+  console.log("market.priceThresholds:", market.priceThresholds);
   console.log("market.priceFeedId:", market.priceFeedId);
   console.log("market.resolveFrom:", market.resolveFrom.toString());
   console.log("market.resolveTo:", market.resolveTo.toString());
@@ -132,10 +240,31 @@ export const logMarket = (market) => {
   console.log("market.isResolved:", market.isResolved);
   console.log("market.outcome:", market.outcome);
   console.log("market.subsidyAmount:", market.subsidyAmount.toString());
-  console.log("market.numOutcome0:", market.numOutcome0.toString());
-  console.log("market.numOutcome1:", market.numOutcome1.toString());
-  console.log("market.numOutcome0Held:", market.numOutcome0Held.toString());
-  console.log("market.numOutcome1Held:", market.numOutcome1Held.toString());
-  console.log("market.priceOutcome0:", market.priceOutcome0.toString());
-  console.log("market.priceOutcome1:", market.priceOutcome1.toString());
+  console.log("market.quantities:", market.quantities.map((q) => q.toString()));
+  console.log("market.sharesHeld:", market.sharesHeld.map((s) => s.toString()));
+  console.log("market.prices:", market.prices.map((p) => p.toString()));
+};
+
+// Decodes the events emitted with `emit_cpi!` from the inner instructions of a transaction
+export const getCpiEvents = async (pg: Program<SonicSage>, txHash: string) => {
+  const tx = await pg.provider.connection.getTransaction(txHash, {
+    commitment: "confirmed",
+    maxSupportedTransactionVersion: 0,
+  });
+  const accountKeys = tx.transaction.message.getAccountKeys();
+  const events = [];
+  for (const inner of tx.meta.innerInstructions ?? []) {
+    for (const ix of inner.instructions) {
+      if (!accountKeys.get(ix.programIdIndex).equals(pg.programId)) {
+        continue;
+      }
+      // Skip the discriminator of the event CPI instruction
+      const data = utils.bytes.bs58.decode(ix.data).subarray(8);
+      const event = pg.coder.events.decode(utils.bytes.base64.encode(data));
+      if (event) {
+        events.push(event);
+      }
+    }
+  }
+  return events;
 };
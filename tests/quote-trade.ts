@@ -0,0 +1,132 @@
+import { expect } from "chai";
+import { AnchorProvider, workspace, BN, web3, utils } from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Account } from "@solana/spl-token";
+import { PublicKey } from "@solana/web3.js";
+import { SonicSage } from "../target/types/sonic_sage";
+import {
+  CONFIDENCE_POLICY,
+  MAX_PRICE_AGE,
+  ORACLE_KIND,
+  OUTCOME_LABELS,
+  PRICE_CONDITION,
+  SETTLEMENT_MODE,
+  CREATOR_FEE_BPS,
+  getCollateralMintPda,
+  getCreatorFeeVaultPda,
+  getMarketVaultPda,
+  setupProgram,
+  nowInSeconds,
+  PRICE_EXPONENT,
+} from "./helpers";
+
+// Market timestamps are unix timestamps in seconds
+const ONE_DAY = 60 * 60 * 24;
+// Outcome prices are fixed-point values with 18 decimals
+const HALF = new BN("500000000000000000");
+
+describe("quote trade", () => {
+  const provider = AnchorProvider.local();
+  const pg = workspace.SonicSage as Program<SonicSage>;
+  const programId = pg.programId;
+  const signer = provider.wallet.payer;
+  const [metadataPda] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("metadata")],
+    programId
+  );
+  let metadata;
+  let marketPda: PublicKey;
+  let mint: PublicKey;
+  let signerTokenAccount: Account;
+  let configPda: PublicKey;
+  let protocolFeeVaultPda: PublicKey;
+
+  before(async () => {
+    const x = await setupProgram({ pg, signer });
+    mint = x.mint;
+    signerTokenAccount = x.signerTokenAccount;
+    configPda = x.configPda;
+    protocolFeeVaultPda = x.protocolFeeVaultPda;
+    metadata = await pg.account.metadata.fetch(metadataPda);
+    [marketPda] = web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("market"),
+        signer.publicKey.toBuffer(),
+        metadata.marketCounter.toArrayLike(Buffer, "le", 8),
+      ],
+      programId
+    );
+
+    await pg.methods
+      .createMarket(
+        [new BN(20050)],
+        PRICE_EXPONENT,
+        PRICE_CONDITION,
+        OUTCOME_LABELS,
+        ORACLE_KIND,
+        "0xe62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43",
+        new BN(nowInSeconds() + ONE_DAY),
+        new BN(nowInSeconds() + ONE_DAY * 2),
+        new BN(100),
+        new BN(50),
+        CREATOR_FEE_BPS,
+        null,
+        null,
+        CONFIDENCE_POLICY,
+        MAX_PRICE_AGE,
+        false,
+        [],
+        0,
+        SETTLEMENT_MODE
+      )
+      .accounts({
+        market: marketPda,
+        metadata: metadataPda,
+        config: configPda,
+        mint,
+        collateralMint: getCollateralMintPda(pg, mint),
+        signerTokenAccount: signerTokenAccount.address,
+        marketVault: getMarketVaultPda(pg, marketPda),
+        creatorFeeVault: getCreatorFeeVaultPda(pg, marketPda),
+        signer: signer.publicKey,
+        tokenProgram: utils.token.TOKEN_PROGRAM_ID,
+        associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
+        systemProgram: web3.SystemProgram.programId,
+      })
+      .signers([signer])
+      .rpc();
+  });
+
+  it("quotes a buy without changing the market", async () => {
+    const marketBefore = await pg.account.market.fetch(marketPda);
+
+    const quote = await pg.methods
+      .quoteTrade({ buy: {} }, 0, new BN(10), null)
+      .accounts({ market: marketPda, subsidyMint: mint, config: configPda })
+      .view();
+
+    const market = await pg.account.market.fetch(marketPda);
+    expect(quote.amount.gt(new BN(0))).to.be.true;
+    expect(quote.averagePrice.gt(HALF)).to.be.true;
+    expect(quote.prices[0].gt(HALF)).to.be.true;
+    expect(quote.prices[1].lt(HALF)).to.be.true;
+    expect(market.quantities[0].toString()).to.equal(
+      marketBefore.quantities[0].toString()
+    );
+  });
+
+  it("fails to quote a trade that would be rejected", async () => {
+    let error;
+    await pg.methods
+      .quoteTrade({ buy: {} }, 0, new BN(10), new BN(nowInSeconds() - 60))
+      .accounts({ market: marketPda, subsidyMint: mint, config: configPda })
+      .view()
+      .catch((err) => {
+        error = err;
+      });
+
+    // A failed simulation carries the program logs with the error
+    const logs = error.logs ?? error.simulationResponse?.logs ?? [];
+    expect(logs.join("\n")).to.include("TradeExpired");
+  });
+});
@@ -4,11 +4,27 @@ import { Program } from "@coral-xyz/anchor";
 import { Account, getAccount, getMint } from "@solana/spl-token";
 import { PublicKey } from "@solana/web3.js";
 import { SonicSage } from "../target/types/sonic_sage";
-import { logMarket, setupProgram } from "./helpers";
+import {
+  CONFIDENCE_POLICY,
+  MAX_PRICE_AGE,
+  ORACLE_KIND,
+  OUTCOME_LABELS,
+  PRICE_CONDITION,
+  SETTLEMENT_MODE,
+  CREATOR_FEE_BPS,
+  getCollateralMintPda,
+  getCreatorFeeVaultPda,
+  getMarketVaultPda,
+  logMarket,
+  setupProgram,
+  nowInSeconds,
+  PRICE_EXPONENT,
+} from "./helpers";
 
-const ONE_DAY = 1000 * 60 * 60 * 24;
+// Market timestamps are unix timestamps in seconds
+const ONE_DAY = 60 * 60 * 24;
 
-describe.only("redeem outcome shares", () => {
+describe("redeem outcome shares", () => {
   const provider = AnchorProvider.local();
   const connection = provider.connection;
   const pg = workspace.SonicSage as Program<SonicSage>;
@@ -18,19 +34,19 @@ describe.only("redeem outcome shares", () => {
     [Buffer.from("metadata")],
     programId
   );
-  const [programTokenAccountPda] = web3.PublicKey.findProgramAddressSync(
-    [Buffer.from("token")],
-    programId
-  );
   let metadata;
   let marketPda: PublicKey;
   let mint: PublicKey;
   let signerTokenAccount: Account;
+  let configPda: PublicKey;
+  let protocolFeeVaultPda: PublicKey;
 
   before(async () => {
     const x = await setupProgram({ pg, signer });
     mint = x.mint;
     signerTokenAccount = x.signerTokenAccount;
+    configPda = x.configPda;
+    protocolFeeVaultPda = x.protocolFeeVaultPda;
     metadata = await pg.account.metadata.fetch(metadataPda);
     [marketPda] = web3.PublicKey.findProgramAddressSync(
       [
@@ -43,19 +59,24 @@ describe.only("redeem outcome shares", () => {
   });
 
   it.skip("redeem successfully", async () => {
-    const price = 200.5;
-    const resolveFrom = new BN(Date.now() + ONE_DAY);
-    const resolveTo = new BN(Date.now() + ONE_DAY * 2);
+    const priceThresholds = [new BN(20050)];
+    // Open the resolve window shortly after creation so the market can be resolved
+    const resolveFrom = new BN(nowInSeconds() + 5);
+    const resolveTo = new BN(nowInSeconds() + ONE_DAY);
     const subsidyAmount = new BN(100);
+    const liquidityB = new BN(50);
     const priceFeedId =
       "0xe62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43";
 
     const createMarketAccounts = {
       market: marketPda,
       metadata: metadataPda,
+      config: configPda,
       mint,
+      collateralMint: getCollateralMintPda(pg, mint),
       signerTokenAccount: signerTokenAccount.address,
-      programTokenAccount: programTokenAccountPda,
+      marketVault: getMarketVaultPda(pg, marketPda),
+      creatorFeeVault: getCreatorFeeVaultPda(pg, marketPda),
       signer: signer.publicKey,
       tokenProgram: utils.token.TOKEN_PROGRAM_ID,
       associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
@@ -63,7 +84,27 @@ describe.only("redeem outcome shares", () => {
     };
 
     const sig1 = await pg.methods
-      .createMarket(price, priceFeedId, resolveFrom, resolveTo, subsidyAmount)
+      .createMarket(
+        priceThresholds,
+        PRICE_EXPONENT,
+        PRICE_CONDITION,
+        OUTCOME_LABELS,
+        ORACLE_KIND,
+        priceFeedId,
+        resolveFrom,
+        resolveTo,
+        subsidyAmount,
+        liquidityB,
+        CREATOR_FEE_BPS,
+        null,
+        null,
+        CONFIDENCE_POLICY,
+        MAX_PRICE_AGE,
+        false,
+        [],
+        0,
+        SETTLEMENT_MODE
+      )
       .accounts(createMarketAccounts)
       .signers([signer])
       .rpc()
@@ -77,15 +118,16 @@ describe.only("redeem outcome shares", () => {
     let market = await pg.account.market.fetch(marketPda);
     const mintAccount = await getMint(connection, mint);
     const mintAccountDecimals = mintAccount.decimals;
-    let programTokenAccount = await getAccount(
+    let marketVault = await getAccount(
       connection,
-      programTokenAccountPda
+      getMarketVaultPda(pg, marketPda)
     );
 
-    const totalSubsidyProvided = programTokenAccount.amount.toString();
+    const totalSubsidyProvided = marketVault.amount.toString();
     logMarket(market);
 
     const numBuyOutcome0 = subsidyAmount.div(new BN(10));
+    const maxCost = subsidyAmount.mul(new BN(10 ** mintAccountDecimals));
     const [outcomeAccountPda] = await web3.PublicKey.findProgramAddressSync(
       [
         Buffer.from("outcome"),
@@ -98,15 +140,18 @@ describe.only("redeem outcome shares", () => {
       market: marketPda,
       subsidyMint: mint,
       signerTokenAccount: signerTokenAccount.address,
-      programTokenAccount: programTokenAccountPda,
+      marketVault: getMarketVaultPda(pg, marketPda),
       outcomeAccount: outcomeAccountPda,
+      config: configPda,
+      protocolFeeVault: protocolFeeVaultPda,
+      creatorFeeVault: getCreatorFeeVaultPda(pg, marketPda),
       signer: signer.publicKey,
       tokenProgram: utils.token.TOKEN_PROGRAM_ID,
       associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
       systemProgram: web3.SystemProgram.programId,
     };
     const sig2 = await pg.methods
-      .buyOutcome(0, numBuyOutcome0)
+      .buyOutcome(0, numBuyOutcome0, maxCost, null)
       .accounts(buyOutcomeAccounts)
       .signers([signer])
       .rpc()
@@ -119,16 +164,23 @@ describe.only("redeem outcome shares", () => {
       outcomeAccountPda
     );
     market = await pg.account.market.fetch(marketPda);
-    programTokenAccount = await getAccount(connection, programTokenAccountPda);
+    marketVault = await getAccount(
+      connection,
+      getMarketVaultPda(pg, marketPda)
+    );
     console.log("========AFTER BUY OUTCOME========");
     logMarket(market);
 
     const resolveMarketAccounts = {
       market: marketPda,
+      config: configPda,
       signer: signer.publicKey,
       systemProgram: web3.SystemProgram.programId,
     };
 
+    // Wait for the resolve window to open
+    await new Promise((resolve) => setTimeout(resolve, 5000));
+
     const sig3 = await pg.methods
       .resolveMarket()
       .accounts(resolveMarketAccounts)
@@ -146,12 +198,11 @@ describe.only("redeem outcome shares", () => {
       market: marketPda,
       subsidyMint: mint,
       signerTokenAccount: signerTokenAccount.address,
-      programTokenAccount: programTokenAccountPda,
+      marketVault: getMarketVaultPda(pg, marketPda),
       outcomeAccount: outcomeAccountPda,
+      config: configPda,
       signer: signer.publicKey,
       tokenProgram: utils.token.TOKEN_PROGRAM_ID,
-      associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
-      systemProgram: web3.SystemProgram.programId,
     };
     const sig4 = await pg.methods
       .redeemOutcome()
@@ -168,5 +219,8 @@ describe.only("redeem outcome shares", () => {
 
     expect(market.isResolved).to.equal(true);
     expect(market.outcome).to.equal(0);
+    expect(market.sharesHeld[0].toString()).to.equal("0");
+    // The position is settled and its account closed
+    expect(await connection.getAccountInfo(outcomeAccountPda)).to.equal(null);
   });
 });
@@ -0,0 +1,312 @@
+import { expect } from "chai";
+import {
+  AnchorProvider,
+  setProvider,
+  workspace,
+  BN,
+  web3,
+  utils,
+} from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Account } from "@solana/spl-token";
+import { PublicKey } from "@solana/web3.js";
+import { SonicSage } from "../target/types/sonic_sage";
+import {
+  CONFIDENCE_POLICY,
+  MAX_PRICE_AGE,
+  OUTCOME_LABELS,
+  PRICE_CONDITION,
+  PRICE_EXPONENT,
+  PRICE_FEED_ID,
+  SETTLEMENT_MODE,
+  CREATOR_FEE_BPS,
+  getCollateralMintPda,
+  getCreatorFeeVaultPda,
+  getMarketVaultPda,
+  setupProgram,
+  nowInSeconds,
+} from "./helpers";
+
+// Markets of this suite resolve from the mock oracle of the test price feed
+const MOCK_ORACLE_KIND = { mock: {} };
+
+describe("resolve market", () => {
+  const provider = AnchorProvider.local();
+  setProvider(provider);
+  const pg = workspace.SonicSage as Program<SonicSage>;
+  const programId = pg.programId;
+  const signer = provider.wallet.payer;
+  const [metadataPda] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("metadata")],
+    programId
+  );
+  const feedId = Array.from(Buffer.from(PRICE_FEED_ID.slice(2), "hex"));
+  const [mockOraclePda] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("mock_oracle"), Buffer.from(feedId)],
+    programId
+  );
+  let mint: PublicKey;
+  let signerTokenAccount: Account;
+  let configPda: PublicKey;
+  let resolveFrom: number;
+
+  // Creates a market on the mock oracle whose resolve window opens at `resolveFrom` by default
+  const createMockMarket = async (args: {
+    priceThresholds: BN[];
+    condition?: object;
+    outcomeLabels?: string[];
+    confidencePolicy?: object;
+    settlementMode?: object;
+    resolveFrom?: number;
+  }) => {
+    const from = args.resolveFrom ?? resolveFrom;
+    const metadata = await pg.account.metadata.fetch(metadataPda);
+    const [marketPda] = web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("market"),
+        signer.publicKey.toBuffer(),
+        metadata.marketCounter.toArrayLike(Buffer, "le", 8),
+      ],
+      programId
+    );
+
+    await pg.methods
+      .createMarket(
+        args.priceThresholds,
+        PRICE_EXPONENT,
+        args.condition ?? PRICE_CONDITION,
+        args.outcomeLabels ?? OUTCOME_LABELS,
+        MOCK_ORACLE_KIND,
+        PRICE_FEED_ID,
+        new BN(from),
+        new BN(from + 60),
+        new BN(100),
+        new BN(50),
+        CREATOR_FEE_BPS,
+        null,
+        null,
+        args.confidencePolicy ?? CONFIDENCE_POLICY,
+        MAX_PRICE_AGE,
+        false,
+        [],
+        0,
+        args.settlementMode ?? SETTLEMENT_MODE
+      )
+      .accounts({
+        market: marketPda,
+        metadata: metadataPda,
+        config: configPda,
+        mint,
+        collateralMint: getCollateralMintPda(pg, mint),
+        signerTokenAccount: signerTokenAccount.address,
+        marketVault: getMarketVaultPda(pg, marketPda),
+        creatorFeeVault: getCreatorFeeVaultPda(pg, marketPda),
+        signer: signer.publicKey,
+        tokenProgram: utils.token.TOKEN_PROGRAM_ID,
+        associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
+        systemProgram: web3.SystemProgram.programId,
+      })
+      .signers([signer])
+      .rpc();
+
+    return marketPda;
+  };
+
+  // Sets the mock price, at the exponent of the test thresholds, published within the resolve window
+  const setMockPrice = async (price: number, conf: number, publishTime?: number) => {
+    await pg.methods
+      .setMockPrice(
+        feedId,
+        new BN(price),
+        new BN(conf),
+        PRICE_EXPONENT,
+        new BN(publishTime ?? resolveFrom)
+      )
+      .accounts({
+        config: configPda,
+        mockOracle: mockOraclePda,
+        admin: signer.publicKey,
+      })
+      .signers([signer])
+      .rpc();
+  };
+
+  // Resolves a market from the mock oracle, returning the error code if it fails
+  const resolveMarket = async (marketPda: PublicKey) => {
+    let error;
+    await pg.methods
+      .resolveMarket()
+      .accounts({
+        signer: signer.publicKey,
+        market: marketPda,
+        oracle: mockOraclePda,
+        config: configPda,
+      })
+      .signers([signer])
+      .rpc()
+      .catch((err) => {
+        error = err;
+      });
+    return error?.error.errorCode.code;
+  };
+
+  // Markets are created before the resolve window of all of them opens
+  const markets: { [name: string]: PublicKey } = {};
+
+  before(async () => {
+    const x = await setupProgram({ pg, signer });
+    mint = x.mint;
+    signerTokenAccount = x.signerTokenAccount;
+    configPda = x.configPda;
+    resolveFrom = nowInSeconds() + 10;
+
+    // Allow markets on the mock oracle of the test price feed
+    await pg.methods
+      .addAllowedPriceFeed(MOCK_ORACLE_KIND, PRICE_FEED_ID)
+      .accounts({ config: configPda, admin: signer.publicKey })
+      .signers([signer])
+      .rpc()
+      .catch((err) => {
+        console.log(err);
+      });
+
+    markets.reject = await createMockMarket({
+      priceThresholds: [new BN(20050)],
+    });
+    markets.retryLater = await createMockMarket({
+      priceThresholds: [new BN(20050)],
+      confidencePolicy: { ...CONFIDENCE_POLICY, onAmbiguous: { retryLater: {} } },
+    });
+    markets.void = await createMockMarket({
+      priceThresholds: [new BN(20050)],
+      confidencePolicy: { ...CONFIDENCE_POLICY, onAmbiguous: { void: {} } },
+    });
+    markets.thresholds = await createMockMarket({
+      priceThresholds: [new BN(20000), new BN(20100)],
+      outcomeLabels: ["Below 200", "200 to 201", "Above 201"],
+    });
+    markets.greaterThan = await createMockMarket({
+      priceThresholds: [new BN(20050)],
+      condition: { greaterThan: {} },
+      outcomeLabels: ["Yes", "No"],
+    });
+    markets.insideLow = await createMockMarket({
+      priceThresholds: [new BN(20000), new BN(20100)],
+      condition: { inside: {} },
+      outcomeLabels: ["Yes", "No"],
+    });
+    markets.insideHigh = await createMockMarket({
+      priceThresholds: [new BN(20000), new BN(20100)],
+      condition: { inside: {} },
+      outcomeLabels: ["Yes", "No"],
+    });
+
+    // Wait for the resolve window to open
+    const wait = (resolveFrom - nowInSeconds() + 1) * 1000;
+    await new Promise((resolve) => setTimeout(resolve, Math.max(wait, 0)));
+  });
+
+  it("rejects a price whose confidence interval is too wide", async () => {
+    // The confidence interval may be at most 1% of the price
+    await setMockPrice(20000, 201);
+
+    expect(await resolveMarket(markets.reject)).to.equal("ConfidenceTooWide");
+  });
+
+  it("rejects an ambiguous price with the reject policy", async () => {
+    // 200.60 is within 2 * 0.10 of the 200.50 threshold
+    await setMockPrice(20060, 10);
+
+    expect(await resolveMarket(markets.reject)).to.equal(
+      "PriceWithinConfidence"
+    );
+    const market = await pg.account.market.fetch(markets.reject);
+    expect(market.isResolved).to.equal(false);
+  });
+
+  it("resolves on a price outside the confidence band", async () => {
+    // 200.80 is more than 2 * 0.10 above the 200.50 threshold
+    await setMockPrice(20080, 10);
+
+    expect(await resolveMarket(markets.reject)).to.equal(undefined);
+    const market = await pg.account.market.fetch(markets.reject);
+    expect(market.isResolved).to.equal(true);
+    expect(market.isVoided).to.equal(false);
+    expect(market.outcome).to.equal(1);
+  });
+
+  it("retries an ambiguous price later with the retry policy", async () => {
+    await setMockPrice(20060, 10, resolveFrom);
+
+    expect(await resolveMarket(markets.retryLater)).to.equal(undefined);
+    let market = await pg.account.market.fetch(markets.retryLater);
+    expect(market.isResolved).to.equal(false);
+    expect(market.lastAmbiguousPublishTime.toNumber()).to.equal(resolveFrom);
+
+    // The ambiguous price cannot be submitted again
+    await setMockPrice(20000, 10, resolveFrom);
+    expect(await resolveMarket(markets.retryLater)).to.equal(
+      "PriceNotAfterAmbiguousPrice"
+    );
+
+    // A price published later resolves the market
+    await setMockPrice(20000, 10, resolveFrom + 1);
+    expect(await resolveMarket(markets.retryLater)).to.equal(undefined);
+    market = await pg.account.market.fetch(markets.retryLater);
+    expect(market.isResolved).to.equal(true);
+    expect(market.outcome).to.equal(0);
+  });
+
+  it("voids the market on an ambiguous price with the void policy", async () => {
+    await setMockPrice(20040, 10);
+
+    expect(await resolveMarket(markets.void)).to.equal(undefined);
+    const market = await pg.account.market.fetch(markets.void);
+    expect(market.isResolved).to.equal(true);
+    expect(market.isVoided).to.equal(true);
+    expect(market.outcome).to.equal(null);
+  });
+
+  it("resolves the outcome of the thresholds below the price", async () => {
+    await setMockPrice(20060, 1);
+
+    expect(await resolveMarket(markets.thresholds)).to.equal(undefined);
+    const market = await pg.account.market.fetch(markets.thresholds);
+    expect(market.outcome).to.equal(1);
+  });
+
+  it("resolves a binary condition to outcome 0 if it holds", async () => {
+    await setMockPrice(20060, 1);
+
+    expect(await resolveMarket(markets.greaterThan)).to.equal(undefined);
+    const market = await pg.account.market.fetch(markets.greaterThan);
+    expect(market.outcome).to.equal(0);
+  });
+
+  it("resolves a bracket including its low and excluding its high threshold", async () => {
+    // Without a confidence interval a price at a threshold is not ambiguous
+    await setMockPrice(20000, 0);
+    expect(await resolveMarket(markets.insideLow)).to.equal(undefined);
+    let market = await pg.account.market.fetch(markets.insideLow);
+    expect(market.outcome).to.equal(0);
+
+    await setMockPrice(20100, 0);
+    expect(await resolveMarket(markets.insideHigh)).to.equal(undefined);
+    market = await pg.account.market.fetch(markets.insideHigh);
+    expect(market.outcome).to.equal(1);
+  });
+
+  it("rejects a categorical time-weighted average market that is not voided on an ambiguous average", async () => {
+    let error;
+    await createMockMarket({
+      priceThresholds: [new BN(20050)],
+      confidencePolicy: { ...CONFIDENCE_POLICY, onAmbiguous: { retryLater: {} } },
+      settlementMode: { twap: {} },
+      resolveFrom: nowInSeconds() + 60,
+    }).catch((err) => {
+      error = err;
+    });
+
+    expect(error.error.errorCode.code).to.equal("InvalidSettlementMode");
+  });
+});
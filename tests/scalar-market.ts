@@ -0,0 +1,237 @@
+import { expect } from "chai";
+import {
+  AnchorProvider,
+  setProvider,
+  workspace,
+  BN,
+  web3,
+  utils,
+} from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Account } from "@solana/spl-token";
+import { PublicKey } from "@solana/web3.js";
+import { SonicSage } from "../target/types/sonic_sage";
+import {
+  CONFIDENCE_POLICY,
+  MAX_PRICE_AGE,
+  ORACLE_KIND,
+  SETTLEMENT_MODE,
+  CREATOR_FEE_BPS,
+  getCollateralMintPda,
+  getCreatorFeeVaultPda,
+  getMarketVaultPda,
+  setupProgram,
+  nowInSeconds,
+} from "./helpers";
+
+// Market timestamps are unix timestamps in seconds
+const ONE_DAY = 60 * 60 * 24;
+// Outcome prices are fixed-point values with 18 decimals
+const HALF = new BN("500000000000000000");
+
+describe("scalar market", () => {
+  const provider = AnchorProvider.local();
+  setProvider(provider);
+  const pg = workspace.SonicSage as Program<SonicSage>;
+  const programId = pg.programId;
+  const signer = provider.wallet.payer;
+  const [metadataPda] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("metadata")],
+    programId
+  );
+  const priceFeedId =
+    "0xe62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43";
+  let metadata;
+  let marketPda: PublicKey;
+  let mint: PublicKey;
+  let signerTokenAccount: Account;
+  let configPda: PublicKey;
+  let protocolFeeVaultPda: PublicKey;
+  let tradingOpensAt: BN;
+
+  const createMarketAccounts = () => ({
+    market: marketPda,
+    metadata: metadataPda,
+    config: configPda,
+    mint,
+    collateralMint: getCollateralMintPda(pg, mint),
+    signerTokenAccount: signerTokenAccount.address,
+    marketVault: getMarketVaultPda(pg, marketPda),
+    creatorFeeVault: getCreatorFeeVaultPda(pg, marketPda),
+    signer: signer.publicKey,
+    tokenProgram: utils.token.TOKEN_PROGRAM_ID,
+    associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
+    systemProgram: web3.SystemProgram.programId,
+  });
+
+  before(async () => {
+    const x = await setupProgram({ pg, signer });
+    mint = x.mint;
+    signerTokenAccount = x.signerTokenAccount;
+    configPda = x.configPda;
+    protocolFeeVaultPda = x.protocolFeeVaultPda;
+
+    metadata = await pg.account.metadata.fetch(metadataPda);
+    [marketPda] = web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("market"),
+        signer.publicKey.toBuffer(),
+        metadata.marketCounter.toArrayLike(Buffer, "le", 8),
+      ],
+      programId
+    );
+  });
+
+  it("rejects an empty range", async () => {
+    let error;
+    await pg.methods
+      .createScalarMarket(
+        new BN(250),
+        new BN(150),
+        0,
+        ORACLE_KIND,
+        priceFeedId,
+        new BN(nowInSeconds() + ONE_DAY),
+        new BN(nowInSeconds() + ONE_DAY * 2),
+        new BN(100),
+        new BN(50),
+        CREATOR_FEE_BPS,
+        null,
+        null,
+        CONFIDENCE_POLICY,
+        MAX_PRICE_AGE,
+        false,
+        [],
+        0,
+        SETTLEMENT_MODE
+      )
+      .accounts(createMarketAccounts())
+      .signers([signer])
+      .rpc()
+      .catch((err) => {
+        error = err;
+      });
+
+    expect(error.error.errorCode.code).to.equal("InvalidScalarRange");
+  });
+
+  it("rejects a price exponent out of range", async () => {
+    let error;
+    await pg.methods
+      .createScalarMarket(
+        new BN(150),
+        new BN(250),
+        19,
+        ORACLE_KIND,
+        priceFeedId,
+        new BN(nowInSeconds() + ONE_DAY),
+        new BN(nowInSeconds() + ONE_DAY * 2),
+        new BN(100),
+        new BN(50),
+        CREATOR_FEE_BPS,
+        null,
+        null,
+        CONFIDENCE_POLICY,
+        MAX_PRICE_AGE,
+        false,
+        [],
+        0,
+        SETTLEMENT_MODE
+      )
+      .accounts(createMarketAccounts())
+      .signers([signer])
+      .rpc()
+      .catch((err) => {
+        error = err;
+      });
+
+    expect(error.error.errorCode.code).to.equal("InvalidPriceExponent");
+  });
+
+  it("create scalar market", async () => {
+    const lower = new BN(150);
+    const upper = new BN(250);
+    const subsidyAmount = new BN(100);
+    // Trading opens an hour after creation
+    tradingOpensAt = new BN(nowInSeconds() + 60 * 60);
+
+    await pg.methods
+      .createScalarMarket(
+        lower,
+        upper,
+        0,
+        ORACLE_KIND,
+        priceFeedId,
+        new BN(nowInSeconds() + ONE_DAY),
+        new BN(nowInSeconds() + ONE_DAY * 2),
+        subsidyAmount,
+        new BN(50),
+        CREATOR_FEE_BPS,
+        tradingOpensAt,
+        null,
+        CONFIDENCE_POLICY,
+        MAX_PRICE_AGE,
+        false,
+        [],
+        0,
+        SETTLEMENT_MODE
+      )
+      .accounts(createMarketAccounts())
+      .signers([signer])
+      .rpc();
+
+    const market = await pg.account.market.fetch(marketPda);
+
+    expect(market.kind.scalar.lower.toString()).to.equal(lower.toString());
+    expect(market.kind.scalar.upper.toString()).to.equal(upper.toString());
+    expect(market.priceExponent).to.equal(0);
+    expect(market.priceThresholds).to.deep.equal([]);
+    expect(market.outcomeLabels).to.deep.equal(["SHORT", "LONG"]);
+    expect(market.numOutcomes).to.equal(2);
+    expect(market.quantities[0].toString()).to.equal(subsidyAmount.toString());
+    expect(market.quantities[1].toString()).to.equal(subsidyAmount.toString());
+    expect(market.prices[0].toString()).to.equal(HALF.toString());
+    expect(market.prices[1].toString()).to.equal(HALF.toString());
+    expect(market.scalarPayout.toString()).to.equal("0");
+    expect(market.isResolved).to.equal(false);
+    expect(market.tradingOpensAt.toString()).to.equal(
+      tradingOpensAt.toString()
+    );
+  });
+
+  it("rejects trades before trading opens", async () => {
+    const [outcomeAccountPda] = web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("outcome"),
+        marketPda.toBuffer(),
+        signer.publicKey.toBuffer(),
+      ],
+      programId
+    );
+
+    let error;
+    await pg.methods
+      .buyOutcome(1, new BN(10), new BN(10).pow(new BN(18)), null)
+      .accounts({
+        market: marketPda,
+        subsidyMint: mint,
+        signerTokenAccount: signerTokenAccount.address,
+        marketVault: getMarketVaultPda(pg, marketPda),
+        outcomeAccount: outcomeAccountPda,
+        config: configPda,
+        protocolFeeVault: protocolFeeVaultPda,
+        creatorFeeVault: getCreatorFeeVaultPda(pg, marketPda),
+        signer: signer.publicKey,
+        tokenProgram: utils.token.TOKEN_PROGRAM_ID,
+        associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
+        systemProgram: web3.SystemProgram.programId,
+      })
+      .signers([signer])
+      .rpc()
+      .catch((err) => {
+        error = err;
+      });
+
+    expect(error.error.errorCode.code).to.equal("TradingNotOpen");
+  });
+});
@@ -7,15 +7,7 @@ import {
   web3,
 } from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
-import {
-  getOrCreateAssociatedTokenAccount,
-  Account,
-  TOKEN_PROGRAM_ID,
-  getAccount,
-} from "@solana/spl-token";
-import { LAMPORTS_PER_SOL, PublicKey } from "@solana/web3.js";
 import { SonicSage } from "../target/types/sonic_sage";
-import { createToken, mintToken } from "./helpers";
 
 describe.skip("setup", () => {
   const provider = AnchorProvider.local();
@@ -28,43 +20,16 @@ describe.skip("setup", () => {
     [Buffer.from("metadata")],
     programId
   );
-  const [tokenPda] = web3.PublicKey.findProgramAddressSync(
-    [Buffer.from("token")],
+  const [configPda] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("config")],
     programId
   );
-  let mint: PublicKey;
-  let signerTokenAccount: Account;
-
-  before(async () => {
-    mint = await createToken({
-      connection,
-      owner: signerKp,
-    });
-
-    signerTokenAccount = await getOrCreateAssociatedTokenAccount(
-      connection,
-      signerKp,
-      mint,
-      signerKp.publicKey
-    );
-  });
 
   it("setup", async () => {
-    await mintToken({
-      connection,
-      mint,
-      signer: signerKp,
-      mintAuthority: signerKp.publicKey,
-      tokenAccount: signerTokenAccount,
-      amount: 100 * LAMPORTS_PER_SOL,
-    });
-
     const accounts = {
       metadata: metadataPda,
-      tokenAccount: tokenPda,
-      mint,
-      signer: signerKp.publicKey,
-      tokenProgram: TOKEN_PROGRAM_ID,
+      config: configPda,
+      admin: signerKp.publicKey,
       systemProgram: web3.SystemProgram.programId,
     };
 
@@ -79,11 +44,7 @@ describe.skip("setup", () => {
     console.log(`txHash: ${txHash}`);
 
     const metadata = await pg.account.metadata.fetch(metadataPda);
-    const token = await getAccount(connection, tokenPda);
 
     expect(metadata.marketCounter.toNumber()).to.equal(0);
-    expect(token.amount).to.equal(BigInt(0));
-    expect(token.owner.equals(tokenPda)).to.be.true;
-    expect(token.address.equals(tokenPda)).to.be.true;
   });
 });
@@ -0,0 +1,320 @@
+import { expect } from "chai";
+import {
+  AnchorProvider,
+  setProvider,
+  workspace,
+  BN,
+  web3,
+  utils,
+} from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import {
+  Account,
+  createInitializeMintInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  ExtensionType,
+  getAccount,
+  getMintLen,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+  TOKEN_2022_PROGRAM_ID,
+} from "@solana/spl-token";
+import { Keypair, PublicKey } from "@solana/web3.js";
+import { SonicSage } from "../target/types/sonic_sage";
+import {
+  CONFIDENCE_POLICY,
+  MAX_PRICE_AGE,
+  ORACLE_KIND,
+  OUTCOME_LABELS,
+  PRICE_CONDITION,
+  SETTLEMENT_MODE,
+  CREATOR_FEE_BPS,
+  getCollateralMintPda,
+  getCreatorFeeVaultPda,
+  getMarketVaultPda,
+  nowInSeconds,
+  setupProgram,
+  PRICE_EXPONENT,
+} from "./helpers";
+
+// Market timestamps are unix timestamps in seconds
+const ONE_DAY = 60 * 60 * 24;
+const DECIMALS = 6;
+// Transfer fee of the Token-2022 mint, in basis points
+const TRANSFER_FEE_BPS = 100;
+
+describe("token-2022 collateral", () => {
+  const provider = AnchorProvider.local();
+  setProvider(provider);
+  const connection = provider.connection;
+  const pg = workspace.SonicSage as Program<SonicSage>;
+  const programId = pg.programId;
+  const signer = provider.wallet.payer;
+  const [metadataPda] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("metadata")],
+    programId
+  );
+  let marketPda: PublicKey;
+  let mint: PublicKey;
+  let signerTokenAccount: Account;
+  let configPda: PublicKey;
+  let protocolFeeVaultPda: PublicKey;
+
+  before(async () => {
+    const x = await setupProgram({ pg, signer });
+    configPda = x.configPda;
+
+    // Create a Token-2022 mint with a transfer fee
+    const mintKeypair = Keypair.generate();
+    mint = mintKeypair.publicKey;
+    const mintLen = getMintLen([ExtensionType.TransferFeeConfig]);
+    const lamports = await connection.getMinimumBalanceForRentExemption(
+      mintLen
+    );
+    const tx = new web3.Transaction().add(
+      web3.SystemProgram.createAccount({
+        fromPubkey: signer.publicKey,
+        newAccountPubkey: mint,
+        space: mintLen,
+        lamports,
+        programId: TOKEN_2022_PROGRAM_ID,
+      }),
+      createInitializeTransferFeeConfigInstruction(
+        mint,
+        signer.publicKey,
+        signer.publicKey,
+        TRANSFER_FEE_BPS,
+        BigInt(10 ** 12),
+        TOKEN_2022_PROGRAM_ID
+      ),
+      createInitializeMintInstruction(
+        mint,
+        DECIMALS,
+        signer.publicKey,
+        null,
+        TOKEN_2022_PROGRAM_ID
+      )
+    );
+    await web3.sendAndConfirmTransaction(connection, tx, [
+      signer,
+      mintKeypair,
+    ]);
+
+    signerTokenAccount = await getOrCreateAssociatedTokenAccount(
+      connection,
+      signer,
+      mint,
+      signer.publicKey,
+      false,
+      undefined,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    await mintTo(
+      connection,
+      signer,
+      mint,
+      signerTokenAccount.address,
+      signer,
+      10000 * 10 ** DECIMALS,
+      [],
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    [protocolFeeVaultPda] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("protocol_fees"), mint.toBuffer()],
+      programId
+    );
+    await pg.methods
+      .addCollateralMint()
+      .accounts({
+        config: configPda,
+        collateralMint: getCollateralMintPda(pg, mint),
+        protocolFeeVault: protocolFeeVaultPda,
+        mint,
+        admin: signer.publicKey,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        systemProgram: web3.SystemProgram.programId,
+      })
+      .signers([signer])
+      .rpc();
+
+    const metadata = await pg.account.metadata.fetch(metadataPda);
+    [marketPda] = web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("market"),
+        signer.publicKey.toBuffer(),
+        metadata.marketCounter.toArrayLike(Buffer, "le", 8),
+      ],
+      programId
+    );
+  });
+
+  it("vault receives the full subsidy of a mint with a transfer fee", async () => {
+    const subsidyAmount = new BN(100);
+
+    await pg.methods
+      .createMarket(
+        [new BN(20050)],
+        PRICE_EXPONENT,
+        PRICE_CONDITION,
+        OUTCOME_LABELS,
+        ORACLE_KIND,
+        "0xe62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43",
+        new BN(nowInSeconds() + ONE_DAY),
+        new BN(nowInSeconds() + ONE_DAY * 2),
+        subsidyAmount,
+        new BN(50),
+        CREATOR_FEE_BPS,
+        null,
+        null,
+        CONFIDENCE_POLICY,
+        MAX_PRICE_AGE,
+        false,
+        [],
+        0,
+        SETTLEMENT_MODE
+      )
+      .accounts({
+        market: marketPda,
+        metadata: metadataPda,
+        config: configPda,
+        mint,
+        collateralMint: getCollateralMintPda(pg, mint),
+        signerTokenAccount: signerTokenAccount.address,
+        marketVault: getMarketVaultPda(pg, marketPda),
+        creatorFeeVault: getCreatorFeeVaultPda(pg, marketPda),
+        signer: signer.publicKey,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
+        systemProgram: web3.SystemProgram.programId,
+      })
+      .signers([signer])
+      .rpc();
+
+    const market = await pg.account.market.fetch(marketPda);
+    const marketVault = await getAccount(
+      connection,
+      getMarketVaultPda(pg, marketPda),
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    const subsidyInTokens = subsidyAmount.mul(new BN(10 ** DECIMALS));
+
+    expect(market.collateralMint.equals(mint)).to.be.true;
+    expect(marketVault.amount.toString()).to.equal(subsidyInTokens.toString());
+    expect(market.currentBalance.toString()).to.equal(
+      marketVault.amount.toString()
+    );
+  });
+
+  it("trades within slippage limits that include the transfer fee", async () => {
+    const [outcomeAccountPda] = web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("outcome"),
+        marketPda.toBuffer(),
+        signer.publicKey.toBuffer(),
+      ],
+      programId
+    );
+    const creatorFeeVaultPda = getCreatorFeeVaultPda(pg, marketPda);
+    const tradeAccounts = {
+      market: marketPda,
+      subsidyMint: mint,
+      signerTokenAccount: signerTokenAccount.address,
+      marketVault: getMarketVaultPda(pg, marketPda),
+      outcomeAccount: outcomeAccountPda,
+      config: configPda,
+      protocolFeeVault: protocolFeeVaultPda,
+      creatorFeeVault: creatorFeeVaultPda,
+      signer: signer.publicKey,
+      tokenProgram: TOKEN_2022_PROGRAM_ID,
+      associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
+      systemProgram: web3.SystemProgram.programId,
+    };
+    const tokenBalance = async (address: PublicKey) =>
+      new BN(
+        (
+          await getAccount(connection, address, undefined, TOKEN_2022_PROGRAM_ID)
+        ).amount.toString()
+      );
+
+    // The debit of a buy, transfer fees included, stays within the collateral amount
+    const collateralAmount = new BN(25 * 10 ** DECIMALS);
+    let balanceBefore = await tokenBalance(signerTokenAccount.address);
+    await pg.methods
+      .buyOutcomeExactIn(0, collateralAmount, new BN(1), null)
+      .accounts(tradeAccounts)
+      .signers([signer])
+      .rpc();
+    const spent = balanceBefore.sub(
+      await tokenBalance(signerTokenAccount.address)
+    );
+    expect(spent.lte(collateralAmount)).to.be.true;
+
+    // The amount received from a sell, after the transfer fee, meets the minimum proceeds
+    const minProceeds = new BN(5 * 10 ** DECIMALS);
+    balanceBefore = await tokenBalance(signerTokenAccount.address);
+    await pg.methods
+      .sellOutcomeExactOut(0, minProceeds, new BN(1000), null)
+      .accounts(tradeAccounts)
+      .signers([signer])
+      .rpc();
+    const received = (await tokenBalance(signerTokenAccount.address)).sub(
+      balanceBefore
+    );
+    expect(received.gte(minProceeds)).to.be.true;
+
+    // The fee vaults hold exactly the fees accrued by the market
+    const market = await pg.account.market.fetch(marketPda);
+    expect((await tokenBalance(protocolFeeVaultPda)).toString()).to.equal(
+      market.protocolFeesAccrued.toString()
+    );
+    expect((await tokenBalance(creatorFeeVaultPda)).toString()).to.equal(
+      market.creatorFeesAccrued.toString()
+    );
+    expect(
+      (await tokenBalance(getMarketVaultPda(pg, marketPda))).toString()
+    ).to.equal(market.currentBalance.toString());
+
+    // A sell whose proceeds after the transfer fee fall short of the minimum is rejected
+    const outcomeAccount = await pg.account.outcomeAccount.fetch(
+      outcomeAccountPda
+    );
+    const sharesToSell = new BN(1);
+    const quote = await pg.methods
+      .quoteTrade({ sell: {} }, 0, sharesToSell, null)
+      .accounts({ market: marketPda, subsidyMint: mint, config: configPda })
+      .view();
+    let error;
+    await pg.methods
+      .sellOutcome(
+        0,
+        sharesToSell,
+        quote.amount.sub(quote.protocolFee).sub(quote.creatorFee),
+        null
+      )
+      .accounts(tradeAccounts)
+      .signers([signer])
+      .rpc()
+      .catch((err) => {
+        error = err;
+      });
+    expect(outcomeAccount.amounts[0].gte(sharesToSell)).to.be.true;
+    expect(error.error.errorCode.code).to.equal("SlippageExceeded");
+
+    // The quote includes the transfer fee, so the trader receives exactly the quoted amount
+    balanceBefore = await tokenBalance(signerTokenAccount.address);
+    await pg.methods
+      .sellOutcome(0, sharesToSell, quote.amountWithFees, null)
+      .accounts(tradeAccounts)
+      .signers([signer])
+      .rpc();
+    expect(
+      (await tokenBalance(signerTokenAccount.address))
+        .sub(balanceBefore)
+        .toString()
+    ).to.equal(quote.amountWithFees.toString());
+  });
+});
@@ -0,0 +1,286 @@
+import { expect } from "chai";
+import {
+  AnchorProvider,
+  setProvider,
+  workspace,
+  BN,
+  web3,
+  utils,
+} from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Account, getAccount, getMint } from "@solana/spl-token";
+import { PublicKey } from "@solana/web3.js";
+import { SonicSage } from "../target/types/sonic_sage";
+import {
+  CONFIDENCE_POLICY,
+  ORACLE_KIND,
+  OUTCOME_LABELS,
+  PRICE_CONDITION,
+  SETTLEMENT_MODE,
+  CREATOR_FEE_BPS,
+  getCollateralMintPda,
+  getCpiEvents,
+  getCreatorFeeVaultPda,
+  getMarketVaultPda,
+  setupProgram,
+  nowInSeconds,
+  PRICE_EXPONENT,
+} from "./helpers";
+
+describe("void market", () => {
+  const provider = AnchorProvider.local();
+  setProvider(provider);
+  const connection = provider.connection;
+  const pg = workspace.SonicSage as Program<SonicSage>;
+  const programId = pg.programId;
+  const signer = provider.wallet.payer;
+  const [metadataPda] = web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("metadata")],
+    programId
+  );
+  let metadata;
+  let marketPda: PublicKey;
+  let outcomeAccountPda: PublicKey;
+  let mint: PublicKey;
+  let signerTokenAccount: Account;
+  let configPda: PublicKey;
+  let protocolFeeVaultPda: PublicKey;
+
+  before(async () => {
+    const x = await setupProgram({ pg, signer });
+    mint = x.mint;
+    signerTokenAccount = x.signerTokenAccount;
+    configPda = x.configPda;
+    protocolFeeVaultPda = x.protocolFeeVaultPda;
+
+    metadata = await pg.account.metadata.fetch(metadataPda);
+    [marketPda] = web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("market"),
+        signer.publicKey.toBuffer(),
+        metadata.marketCounter.toArrayLike(Buffer, "le", 8),
+      ],
+      programId
+    );
+    [outcomeAccountPda] = web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("outcome"),
+        marketPda.toBuffer(),
+        signer.publicKey.toBuffer(),
+      ],
+      programId
+    );
+
+    // Short resolve window that closes before anyone resolves the market,
+    // with a maximum price age of one second after which it can be voided
+    const subsidyAmount = new BN(100);
+    await pg.methods
+      .createMarket(
+        [new BN(20050)],
+        PRICE_EXPONENT,
+        PRICE_CONDITION,
+        OUTCOME_LABELS,
+        ORACLE_KIND,
+        "0xe62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43",
+        new BN(nowInSeconds() + 2),
+        new BN(nowInSeconds() + 4),
+        subsidyAmount,
+        new BN(50),
+        CREATOR_FEE_BPS,
+        null,
+        null,
+        CONFIDENCE_POLICY,
+        new BN(1),
+        false,
+        [],
+        0,
+        SETTLEMENT_MODE
+      )
+      .accounts({
+        market: marketPda,
+        metadata: metadataPda,
+        config: configPda,
+        mint,
+        collateralMint: getCollateralMintPda(pg, mint),
+        signerTokenAccount: signerTokenAccount.address,
+        marketVault: getMarketVaultPda(pg, marketPda),
+        creatorFeeVault: getCreatorFeeVaultPda(pg, marketPda),
+        signer: signer.publicKey,
+        tokenProgram: utils.token.TOKEN_PROGRAM_ID,
+        associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
+        systemProgram: web3.SystemProgram.programId,
+      })
+      .signers([signer])
+      .rpc();
+
+    const mintAccount = await getMint(connection, mint);
+    await pg.methods
+      .buyOutcome(
+        0,
+        new BN(10),
+        subsidyAmount.mul(new BN(10 ** mintAccount.decimals)),
+        null
+      )
+      .accounts({
+        market: marketPda,
+        subsidyMint: mint,
+        signerTokenAccount: signerTokenAccount.address,
+        marketVault: getMarketVaultPda(pg, marketPda),
+        outcomeAccount: outcomeAccountPda,
+        config: configPda,
+        protocolFeeVault: protocolFeeVaultPda,
+        creatorFeeVault: getCreatorFeeVaultPda(pg, marketPda),
+        signer: signer.publicKey,
+        tokenProgram: utils.token.TOKEN_PROGRAM_ID,
+        associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
+        systemProgram: web3.SystemProgram.programId,
+      })
+      .signers([signer])
+      .rpc();
+  });
+
+  it("tracks the cost basis of a position", async () => {
+    const market = await pg.account.market.fetch(marketPda);
+    const outcomeAccount = await pg.account.outcomeAccount.fetch(
+      outcomeAccountPda
+    );
+
+    expect(outcomeAccount.costBasis.gtn(0)).to.be.true;
+    expect(market.totalCostBasis.toString()).to.equal(
+      outcomeAccount.costBasis.toString()
+    );
+  });
+
+  it("rejects voiding a market that can still be resolved", async () => {
+    let error;
+    await pg.methods
+      .voidMarket()
+      .accounts({
+        market: marketPda,
+        config: configPda,
+        signer: signer.publicKey,
+      })
+      .signers([signer])
+      .rpc()
+      .catch((err) => {
+        error = err;
+      });
+
+    expect(error.error.errorCode.code).to.equal("MarketResolvable");
+  });
+
+  it("rejects trades once the resolve window opens", async () => {
+    // Trading closes when the resolve window opens
+    await new Promise((resolve) => setTimeout(resolve, 3000));
+
+    const mintAccount = await getMint(connection, mint);
+    let error;
+    await pg.methods
+      .buyOutcome(
+        0,
+        new BN(10),
+        new BN(100).mul(new BN(10 ** mintAccount.decimals)),
+        null
+      )
+      .accounts({
+        market: marketPda,
+        subsidyMint: mint,
+        signerTokenAccount: signerTokenAccount.address,
+        marketVault: getMarketVaultPda(pg, marketPda),
+        outcomeAccount: outcomeAccountPda,
+        config: configPda,
+        protocolFeeVault: protocolFeeVaultPda,
+        creatorFeeVault: getCreatorFeeVaultPda(pg, marketPda),
+        signer: signer.publicKey,
+        tokenProgram: utils.token.TOKEN_PROGRAM_ID,
+        associatedTokenProgram: utils.token.ASSOCIATED_PROGRAM_ID,
+        systemProgram: web3.SystemProgram.programId,
+      })
+      .signers([signer])
+      .rpc()
+      .catch((err) => {
+        error = err;
+      });
+
+    expect(error.error.errorCode.code).to.equal("TradingClosed");
+  });
+
+  it("void market after the resolve window", async () => {
+    // Wait for the resolve window to close and its last price to become too old
+    await new Promise((resolve) => setTimeout(resolve, 4000));
+
+    const txHash = await pg.methods
+      .voidMarket()
+      .accounts({
+        market: marketPda,
+        config: configPda,
+        signer: signer.publicKey,
+      })
+      .signers([signer])
+      .rpc({ commitment: "confirmed" });
+
+    const market = await pg.account.market.fetch(marketPda);
+    expect(market.isResolved).to.equal(true);
+    expect(market.isVoided).to.equal(true);
+
+    // Voiding is emitted as an event for indexers
+    const [voidedEvent] = (await getCpiEvents(pg, txHash)).filter(
+      (event) => event.name === "marketVoided"
+    );
+    expect(voidedEvent.data.market.equals(marketPda)).to.be.true;
+    expect(
+      voidedEvent.data.voidedAt.gt(market.resolveTo.add(market.maxPriceAge))
+    ).to.be.true;
+  });
+
+  it("refunds the cost basis and reclaims the subsidy", async () => {
+    const outcomeAccountBefore = await pg.account.outcomeAccount.fetch(
+      outcomeAccountPda
+    );
+    const balanceBefore = (
+      await getAccount(connection, signerTokenAccount.address)
+    ).amount;
+
+    await pg.methods
+      .refundOutcome()
+      .accounts({
+        market: marketPda,
+        subsidyMint: mint,
+        signerTokenAccount: signerTokenAccount.address,
+        marketVault: getMarketVaultPda(pg, marketPda),
+        outcomeAccount: outcomeAccountPda,
+        config: configPda,
+        signer: signer.publicKey,
+        tokenProgram: utils.token.TOKEN_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc();
+
+    const balanceAfterRefund = (
+      await getAccount(connection, signerTokenAccount.address)
+    ).amount;
+    expect((balanceAfterRefund - balanceBefore).toString()).to.equal(
+      outcomeAccountBefore.costBasis.toString()
+    );
+    // The position is settled and its account closed
+    expect(await connection.getAccountInfo(outcomeAccountPda)).to.equal(null);
+
+    await pg.methods
+      .reclaimSubsidy()
+      .accounts({
+        market: marketPda,
+        subsidyMint: mint,
+        creatorTokenAccount: signerTokenAccount.address,
+        marketVault: getMarketVaultPda(pg, marketPda),
+        config: configPda,
+        creator: signer.publicKey,
+        tokenProgram: utils.token.TOKEN_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc();
+
+    const market = await pg.account.market.fetch(marketPda);
+    expect(market.currentBalance.toString()).to.equal("0");
+    expect(market.totalCostBasis.toString()).to.equal("0");
+  });
+});